@@ -0,0 +1,591 @@
+use crate::gfx::{
+    swap_animation, Animation, AnimationFinished, AnimationResource, AnimationType, SpriteAdded,
+    SpriteMeta,
+};
+use crate::presentation::FACE_VELOCITY_MIN_SPEED;
+use bevy::{
+    ecs::system::{EntityCommands, SystemId},
+    prelude::*,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Requests animation changes by name: set `pending` and update_animation_controllers swaps the
+/// Animation component. Requesting the animation that is already `current` doesn't restart it
+///
+/// ```ignore
+/// controller.pending = Some("run".into());
+/// ```
+#[derive(Debug, Clone, Default, Component)]
+pub struct AnimationController {
+    pub current: String,
+    pub pending: Option<String>,
+}
+
+impl AnimationController {
+    pub fn new(current: impl Into<String>) -> Self {
+        AnimationController {
+            current: current.into(),
+            pending: None,
+        }
+    }
+
+    pub fn play(&mut self, name: impl Into<String>) {
+        self.pending = Some(name.into());
+    }
+}
+
+///
+/// update_animation_controllers: Bevy system
+///
+/// Swaps in the pending animation of each changed AnimationController
+pub fn update_animation_controllers(
+    mut commands: Commands,
+    animation_resource: Res<AnimationResource>,
+    mut query: Query<
+        (Entity, &mut AnimationController, &mut SpriteMeta),
+        Changed<AnimationController>,
+    >,
+) {
+    for (entity, mut controller, mut sprite_meta) in query.iter_mut() {
+        let Some(pending) = controller.pending.take() else {
+            continue;
+        };
+
+        if pending == controller.current {
+            continue;
+        }
+
+        if let Some(animation) = animation_resource.get(&pending) {
+            swap_animation(&mut commands, entity, &mut sprite_meta, animation);
+            controller.current = pending;
+        } else {
+            warn!("Warning: no animation named {} found", pending);
+        }
+    }
+}
+
+/// Facing of a DirectionalAnimation. Displays as the suffix used in animation names, e.g. "up_left"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    #[default]
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::UpLeft => "up_left",
+            Direction::UpRight => "up_right",
+            Direction::DownLeft => "down_left",
+            Direction::DownRight => "down_right",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::Right,
+        Direction::UpRight,
+        Direction::Up,
+        Direction::UpLeft,
+        Direction::Left,
+        Direction::DownLeft,
+        Direction::Down,
+        Direction::DownRight,
+    ];
+
+    /// Angle of the direction in radians, counter-clockwise from Right (y up)
+    pub fn angle(&self) -> f32 {
+        let step = std::f32::consts::FRAC_PI_4;
+        match self {
+            Direction::Right => 0.0,
+            Direction::UpRight => step,
+            Direction::Up => step * 2.0,
+            Direction::UpLeft => step * 3.0,
+            Direction::Left => step * 4.0,
+            Direction::DownLeft => -step * 3.0,
+            Direction::Down => -step * 2.0,
+            Direction::DownRight => -step,
+        }
+    }
+
+    /// The nearest of the 8 directions to a vector, None for a zero vector
+    pub fn from_vec2(vector: Vec2) -> Option<Direction> {
+        if vector.length() < FACE_VELOCITY_MIN_SPEED {
+            return None;
+        }
+
+        let angle = vector.y.atan2(vector.x);
+        Direction::ALL.iter().copied().min_by(|a, b| {
+            angle_between(angle, a.angle()).total_cmp(&angle_between(angle, b.angle()))
+        })
+    }
+
+    /// Like `from_vec2`, but keeps `current` until the vector is more than `hysteresis` radians
+    /// past the edge of its 45 degree sector, so movement close to a diagonal doesn't flicker
+    /// between two directions. A zero vector keeps `current`
+    pub fn from_vec2_with_hysteresis(
+        vector: Vec2,
+        current: Direction,
+        hysteresis: f32,
+    ) -> Direction {
+        let Some(nearest) = Direction::from_vec2(vector) else {
+            return current;
+        };
+
+        let angle = vector.y.atan2(vector.x);
+        if angle_between(angle, current.angle()) <= std::f32::consts::FRAC_PI_8 + hysteresis {
+            current
+        } else {
+            nearest
+        }
+    }
+}
+
+/// Smallest absolute difference between two angles, in radians
+fn angle_between(a: f32, b: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    ((a - b + PI).rem_euclid(TAU) - PI).abs()
+}
+
+/// Default hysteresis of FacingFromVelocity, about 10 degrees
+pub const FACING_HYSTERESIS: f32 = 0.17;
+
+/// Sets DirectionalAnimation.facing from `velocity` (8 directions, with hysteresis in radians),
+/// e.g. for top-down characters. Update `velocity` from your movement code
+#[derive(Debug, Clone, Copy, Component)]
+pub struct FacingFromVelocity {
+    pub velocity: Vec2,
+    pub hysteresis: f32,
+}
+
+impl Default for FacingFromVelocity {
+    fn default() -> Self {
+        FacingFromVelocity {
+            velocity: Vec2::ZERO,
+            hysteresis: FACING_HYSTERESIS,
+        }
+    }
+}
+
+///
+/// update_facing_from_velocity: Bevy system
+///
+/// Updates the facing of every DirectionalAnimation with a FacingFromVelocity, only writing it
+/// when the direction actually changes
+pub fn update_facing_from_velocity(
+    mut query: Query<(&FacingFromVelocity, &mut DirectionalAnimation)>,
+) {
+    query.iter_mut().for_each(|(facing, mut directional)| {
+        let next = Direction::from_vec2_with_hysteresis(
+            facing.velocity,
+            directional.facing,
+            facing.hysteresis,
+        );
+        if next != directional.facing {
+            directional.facing = next;
+        }
+    });
+}
+
+///
+/// DirectionalAnimation
+///
+/// Plays "{base}_{facing}" (e.g. "walk_down") from AnimationResource, and switches animation
+/// whenever `base` or `facing` changes. If that animation doesn't exist `base` itself is played,
+/// and if neither does the current animation is kept (with a warning, once per name)
+///
+/// Turning keeps the frame position, so a walk cycle doesn't restart on every turn
+#[derive(Debug, Clone, Default, Component)]
+pub struct DirectionalAnimation {
+    pub base: String,
+    pub facing: Direction,
+    current: Option<String>,
+    current_base: Option<String>, // base and facing `current` was resolved from
+    current_facing: Option<Direction>,
+}
+
+impl DirectionalAnimation {
+    pub fn new(base: impl Into<String>, facing: Direction) -> Self {
+        DirectionalAnimation {
+            base: base.into(),
+            facing,
+            current: None,
+            current_base: None,
+            current_facing: None,
+        }
+    }
+
+    /// Name of the direction-specific animation, e.g. "walk_down"
+    pub fn resolved_name(&self) -> String {
+        format!("{}_{}", self.base, self.facing)
+    }
+
+    /// Name of the animation currently played, once update_directional_animations has run
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+}
+
+///
+/// update_directional_animations: Bevy system
+///
+/// Swaps in the animation for each changed DirectionalAnimation
+pub fn update_directional_animations(
+    mut commands: Commands,
+    animation_resource: Res<AnimationResource>,
+    mut query: Query<
+        (
+            Entity,
+            &mut DirectionalAnimation,
+            &mut SpriteMeta,
+            Option<&Animation>,
+        ),
+        Changed<DirectionalAnimation>,
+    >,
+    mut warned: Local<HashSet<String>>,
+) {
+    for (entity, mut directional, mut sprite_meta, current_animation) in query.iter_mut() {
+        let resolved = directional.resolved_name();
+        let name = if animation_resource.get(&resolved).is_some() {
+            resolved
+        } else if animation_resource.get(&directional.base).is_some() {
+            directional.base.clone()
+        } else {
+            if warned.insert(resolved.clone()) {
+                warn!(
+                    "Warning: no animation named {} or {} found",
+                    resolved, directional.base
+                );
+            }
+            continue;
+        };
+
+        if directional.current.as_ref() == Some(&name) {
+            continue;
+        }
+
+        let mut animation = animation_resource.get(&name).unwrap();
+        // only a turn keeps the frame position, a new base (e.g. walk -> attack) starts over
+        let turned = directional.current_base.as_ref() == Some(&directional.base)
+            && directional.current_facing != Some(directional.facing);
+        if let Some(current_animation) = current_animation.filter(|_| turned) {
+            animation.set_start_frame(current_animation.frame_position());
+        }
+
+        swap_animation(&mut commands, entity, &mut sprite_meta, animation);
+        let directional = directional.bypass_change_detection();
+        directional.current = Some(name);
+        directional.current_base = Some(directional.base.clone());
+        directional.current_facing = Some(directional.facing);
+    }
+}
+
+/// Animations to play back-to-back, e.g. windup -> loop -> recover
+///
+/// When a Once or OnceReverse animation finishes, the next queued name is taken from
+/// AnimationResource and swapped in (ahead of the finished animation's `then`). Once the queue is
+/// empty the last animation finishes as usual
+#[derive(Debug, Clone, Default, Component)]
+pub struct AnimationQueue(pub VecDeque<String>);
+
+impl AnimationQueue {
+    pub fn new<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        AnimationQueue(names.into_iter().map(|name| name.into()).collect())
+    }
+
+    /// Adds an animation to the end of the queue
+    pub fn push(&mut self, name: impl Into<String>) {
+        self.0.push_back(name.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Pops names until one is found in AnimationResource, warning about any that aren't
+    pub(crate) fn pop_next(&mut self, animation_resource: &AnimationResource) -> Option<Animation> {
+        while let Some(name) = self.0.pop_front() {
+            match animation_resource.get(&name) {
+                Some(animation) => return Some(animation),
+                None => warn!("Warning: no animation named {} found in queue", name),
+            }
+        }
+        None
+    }
+}
+
+/// One-shot systems waiting for an entity's animation to finish, registered with
+/// play_animation_then. Each entity has at most one
+#[derive(Debug, Default, Resource)]
+pub struct AnimationCallbacks {
+    map: HashMap<Entity, (String, SystemId)>,
+}
+
+impl AnimationCallbacks {
+    /// True if the entity has a callback waiting
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.map.contains_key(&entity)
+    }
+
+    /// Removes the entity's callback if it's waiting for this animation
+    fn take(&mut self, entity: Entity, animation: &str) -> Option<SystemId> {
+        match self.map.get(&entity) {
+            Some((name, _)) if name == animation => self.map.remove(&entity).map(|(_, id)| id),
+            _ => None,
+        }
+    }
+}
+
+/// Plays an animation on an entity and runs a callback once it finishes, for scripted sequences
+///
+/// ```ignore
+/// fn open_chest(mut commands: Commands, chest: Query<Entity, With<Chest>>) {
+///     commands
+///         .entity(chest.single())
+///         .play_animation_then("chest_open", |mut events: EventWriter<PlaySFX>| {
+///             events.send(PlaySFX::new("coins"));
+///         });
+/// }
+/// ```
+///
+/// The callback is any system, including an exclusive `|world: &mut World| { .. }`. It's
+/// registered as a one-shot system and runs through Commands in the frame the animation finishes,
+/// after AnimationFinished is sent, and is unregistered afterwards. Since it's stored and run
+/// later it has to be `Send + 'static`: it can't borrow anything from the calling system, so move
+/// in (or clone) what it needs, e.g. the entity id, and fetch everything else through its own
+/// system params. The entity may already be despawned by then (a Despawn animation)
+///
+/// A new play_animation_then on the same entity replaces the waiting callback. If the animation is
+/// replaced by other means, or never finishes (Repeat), the callback is dropped once the entity
+/// is despawned
+pub trait PlayAnimationThenExt {
+    fn play_animation_then<M>(
+        &mut self,
+        animation: impl Into<String>,
+        callback: impl IntoSystem<(), (), M> + Send + 'static,
+    ) -> &mut Self;
+}
+
+impl PlayAnimationThenExt for EntityCommands<'_> {
+    fn play_animation_then<M>(
+        &mut self,
+        animation: impl Into<String>,
+        callback: impl IntoSystem<(), (), M> + Send + 'static,
+    ) -> &mut Self {
+        let name = animation.into();
+
+        self.add(move |entity: Entity, world: &mut World| {
+            let Some(animation) = world
+                .get_resource::<AnimationResource>()
+                .and_then(|animation_resource| animation_resource.get(&name))
+            else {
+                warn!("Warning: no animation named {} found", name);
+                return;
+            };
+            if animation.get_type() == AnimationType::Repeat {
+                warn!(
+                    "Warning: {} repeats, its play_animation_then callback will never run",
+                    name
+                );
+            }
+
+            let Some(mut entity_mut) = world.get_entity_mut(entity) else {
+                return;
+            };
+            match entity_mut.get_mut::<SpriteMeta>() {
+                Some(mut sprite_meta) => {
+                    sprite_meta.index = animation.current_frame();
+                    sprite_meta.set_sheet_name(animation.sheet_name());
+                }
+                None => {
+                    entity_mut.insert(SpriteMeta {
+                        index: animation.current_frame(),
+                        sheet_name: animation.sheet_name().to_string(),
+                        ..default()
+                    });
+                }
+            }
+            if let Some(mut controller) = entity_mut.get_mut::<AnimationController>() {
+                controller.current = name.clone();
+            }
+            entity_mut.remove::<SpriteAdded>().insert(animation);
+
+            // drop callbacks that can no longer run: this entity's previous one, and despawned
+            // entities'
+            let mut callbacks = world
+                .remove_resource::<AnimationCallbacks>()
+                .unwrap_or_default();
+            let stale: Vec<Entity> = callbacks
+                .map
+                .keys()
+                .filter(|other| **other == entity || world.get_entity(**other).is_none())
+                .copied()
+                .collect();
+            for other in stale {
+                if let Some((_, id)) = callbacks.map.remove(&other) {
+                    let _ = world.remove_system(id);
+                }
+            }
+
+            let id = world.register_system(callback);
+            callbacks.map.insert(entity, (name, id));
+            world.insert_resource(callbacks);
+        });
+
+        self
+    }
+}
+
+///
+/// run_animation_callbacks: Bevy system
+///
+/// Runs the play_animation_then callbacks of the animations that finished this frame, then
+/// unregisters them
+pub fn run_animation_callbacks(
+    mut commands: Commands,
+    mut events: EventReader<AnimationFinished>,
+    mut callbacks: ResMut<AnimationCallbacks>,
+) {
+    for event in events.read() {
+        let Some(id) = callbacks.take(event.entity, &event.animation_name) else {
+            continue;
+        };
+
+        commands.run_system(id);
+        commands.add(move |world: &mut World| {
+            let _ = world.remove_system(id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::update_animations;
+    use std::time::Duration;
+
+    #[test]
+    fn only_turns_keep_the_frame_position() {
+        let mut app = crate::testing::test_app();
+        app.add_systems(
+            Update,
+            update_directional_animations.before(update_animations),
+        );
+        let mut animation_resource = AnimationResource::new();
+        for name in ["walking_fast_down", "walking_fast_left", "walk_left"] {
+            animation_resource.insert(
+                name.to_string(),
+                Animation::new(
+                    "sheet".to_string(),
+                    (0..4).collect(),
+                    0.1,
+                    AnimationType::Repeat,
+                ),
+            );
+        }
+        app.insert_resource(animation_resource);
+
+        let entity = app
+            .world
+            .spawn((
+                SpriteMeta {
+                    sheet_name: "sheet".to_string(),
+                    ..default()
+                },
+                DirectionalAnimation::new("walking_fast", Direction::Down),
+            ))
+            .id();
+        let position = |app: &App| app.world.get::<Animation>(entity).unwrap().frame_position();
+        crate::testing::step(&mut app, Duration::ZERO);
+        crate::testing::step(&mut app, Duration::from_millis(100));
+        crate::testing::step(&mut app, Duration::from_millis(100));
+        assert_eq!(position(&app), 2);
+
+        // a turn keeps the position
+        app.world
+            .get_mut::<DirectionalAnimation>(entity)
+            .unwrap()
+            .facing = Direction::Left;
+        crate::testing::step(&mut app, Duration::ZERO);
+        assert_eq!(position(&app), 2);
+
+        // a base that's only a prefix of the old one starts over
+        app.world
+            .get_mut::<DirectionalAnimation>(entity)
+            .unwrap()
+            .base = "walk".to_string();
+        crate::testing::step(&mut app, Duration::ZERO);
+        assert_eq!(position(&app), 0);
+    }
+
+    #[test]
+    fn picks_the_nearest_of_eight_directions() {
+        let velocity = |degrees: f32| Vec2::from_angle(degrees.to_radians()) * 2.0;
+
+        let table = [
+            (0.0, Direction::Right),
+            (20.0, Direction::Right),
+            (30.0, Direction::UpRight),
+            (45.0, Direction::UpRight),
+            (90.0, Direction::Up),
+            (135.0, Direction::UpLeft),
+            (180.0, Direction::Left),
+            (-170.0, Direction::Left),
+            (-135.0, Direction::DownLeft),
+            (-90.0, Direction::Down),
+            (-45.0, Direction::DownRight),
+            (-10.0, Direction::Right),
+        ];
+
+        for (degrees, expected) in table {
+            assert_eq!(
+                Direction::from_vec2(velocity(degrees)),
+                Some(expected),
+                "{} degrees",
+                degrees
+            );
+        }
+        assert_eq!(Direction::from_vec2(Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_facing_near_a_sector_edge() {
+        let velocity = |degrees: f32| Vec2::from_angle(degrees.to_radians()) * 2.0;
+
+        // 25 degrees is past the Right/UpRight edge (22.5) but within the hysteresis
+        let table = [
+            (25.0, Direction::Right, Direction::Right),
+            (30.0, Direction::Right, Direction::Right),
+            (40.0, Direction::Right, Direction::UpRight),
+            (20.0, Direction::UpRight, Direction::UpRight),
+            (5.0, Direction::UpRight, Direction::Right),
+        ];
+        for (degrees, current, expected) in table {
+            assert_eq!(
+                Direction::from_vec2_with_hysteresis(velocity(degrees), current, FACING_HYSTERESIS),
+                expected,
+                "{} degrees facing {}",
+                degrees,
+                current
+            );
+        }
+        assert_eq!(
+            Direction::from_vec2_with_hysteresis(Vec2::ZERO, Direction::Up, FACING_HYSTERESIS),
+            Direction::Up
+        );
+    }
+}
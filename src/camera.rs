@@ -0,0 +1,580 @@
+use crate::gfx::GFXSettings;
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ScalingMode::WindowSize, Viewport},
+        view::RenderLayers,
+    },
+    window::PrimaryWindow,
+};
+
+#[derive(Debug, Component)]
+pub struct MainCamera {}
+
+#[derive(Debug, Component)]
+pub struct HasCameraFocus {}
+
+/// Moves HasCameraFocus to `target` (taking it off every other entity), or clears it with None so
+/// the camera stops following, e.g. to point the camera at an NPC during a cutscene and back
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SetCameraFocus {
+    pub target: Option<Entity>,
+}
+
+impl SetCameraFocus {
+    pub fn new(target: Entity) -> Self {
+        SetCameraFocus {
+            target: Some(target),
+        }
+    }
+
+    pub fn clear() -> Self {
+        SetCameraFocus { target: None }
+    }
+}
+
+///
+/// set_camera_focus: Bevy system
+///
+/// Handles SetCameraFocus. A target that doesn't exist is logged and the focus is cleared
+pub fn set_camera_focus(
+    mut commands: Commands,
+    mut events: EventReader<SetCameraFocus>,
+    query_focus: Query<Entity, With<HasCameraFocus>>,
+    query_entities: Query<Entity>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+
+    query_focus.iter().for_each(|entity| {
+        commands.entity(entity).remove::<HasCameraFocus>();
+    });
+
+    match event.target {
+        Some(target) if query_entities.contains(target) => {
+            commands.entity(target).insert(HasCameraFocus {});
+        }
+        Some(target) => {
+            warn!(
+                "Warning: camera focus target {:?} doesn't exist, the camera stays put",
+                target
+            );
+        }
+        None => {}
+    }
+}
+
+/// Added to the HasCameraFocus entity's position when the camera follows it, in world units
+/// (e.g. `Vec2::new(0.0, 2.0)` keeps the camera two tiles above the player)
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct CameraFocusOffset(pub Vec2);
+
+/// Default GFXPlugin.pixels_per_unit, logical pixels per game unit at a camera scale of 1.0
+pub const PIXELS_PER_UNIT: f32 = 16.0;
+
+pub fn spawn_camera(mut commands: Commands, settings: Res<GFXSettings>) {
+    let camera_bundle = |order: isize| Camera2dBundle {
+        projection: OrthographicProjection {
+            near: -1000.0,
+            far: 1000.0,
+            scaling_mode: WindowSize(settings.pixels_per_unit),
+            ..default()
+        },
+        camera: Camera {
+            clear_color: settings.clear_color.clone(),
+            order,
+            ..default()
+        },
+        ..default()
+    };
+
+    if settings.split_screen == SplitScreen::None {
+        commands.spawn((MainCamera {}, CameraZoom::default(), camera_bundle(0)));
+        return;
+    }
+
+    for index in 0..settings.split_screen.count() {
+        commands.spawn((
+            MainCamera {},
+            SplitCamera { index },
+            CameraZoom::default(),
+            camera_bundle(index as isize),
+        ));
+    }
+}
+
+/// Zoom of a camera, 1.0 = GFXSettings.pixels_per_unit screen pixels per unit, 2.0 = twice as
+/// close. Every MainCamera spawns with one. Kept within CameraZoomLimits when applied
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct CameraZoom(pub f32);
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        CameraZoom(1.0)
+    }
+}
+
+/// Range CameraZoom is clamped to, so the view can't zoom into a few pixels or out past the level
+///
+/// CameraBounds only limits where the camera center goes, not the zoom, so a view zoomed out
+/// wider than the bounds still shows past their edges. Pick `min` with the bounds in mind
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct CameraZoomLimits {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for CameraZoomLimits {
+    fn default() -> Self {
+        CameraZoomLimits {
+            min: 0.1,
+            max: 10.0,
+        }
+    }
+}
+
+///
+/// apply_camera_zoom: Bevy system
+///
+/// Clamps changed CameraZoom values into CameraZoomLimits and writes them to the projection scale
+pub fn apply_camera_zoom(
+    limits: Res<CameraZoomLimits>,
+    mut query_camera: Query<(&mut CameraZoom, &mut OrthographicProjection)>,
+) {
+    let min = limits.min.min(limits.max).max(f32::EPSILON);
+    let max = limits.max.max(min);
+
+    for (mut zoom, mut projection) in query_camera.iter_mut() {
+        if !zoom.is_changed() && !limits.is_changed() {
+            continue;
+        }
+
+        let clamped = zoom.0.clamp(min, max);
+        if clamped != zoom.0 {
+            debug!("Camera zoom {} clamped to {}", zoom.0, clamped);
+            zoom.bypass_change_detection().0 = clamped;
+        }
+
+        let scale = 1.0 / clamped;
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+    }
+}
+
+/// Split-screen layouts for local multiplayer, see GFXPlugin.split_screen
+///
+/// * None: a single MainCamera covering the window
+/// * Horizontal: two cameras side by side
+/// * Vertical: two cameras stacked, player 1 on top
+/// * Quad: four cameras, left to right then top to bottom
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitScreen {
+    #[default]
+    None,
+    Horizontal,
+    Vertical,
+    Quad,
+}
+
+impl SplitScreen {
+    /// Number of cameras in the layout
+    pub fn count(&self) -> usize {
+        match self {
+            SplitScreen::None => 1,
+            SplitScreen::Horizontal | SplitScreen::Vertical => 2,
+            SplitScreen::Quad => 4,
+        }
+    }
+
+    /// Viewport of camera `index` in a window of the given physical size
+    pub fn viewport(&self, window_size: UVec2, index: usize) -> Viewport {
+        let (columns, rows) = match self {
+            SplitScreen::None => (1, 1),
+            SplitScreen::Horizontal => (2, 1),
+            SplitScreen::Vertical => (1, 2),
+            SplitScreen::Quad => (2, 2),
+        };
+        let size = UVec2::new(window_size.x / columns, window_size.y / rows);
+        let cell = UVec2::new(index as u32 % columns, index as u32 / columns);
+
+        Viewport {
+            physical_position: cell * size,
+            physical_size: size,
+            ..default()
+        }
+    }
+}
+
+/// One of the cameras spawned for GFXPlugin.split_screen. Give it a CameraFollow to pick who it
+/// follows
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SplitCamera {
+    pub index: usize, // position in the layout, 0 = player 1
+}
+
+/// Makes a camera follow a specific entity (plus its CameraFocusOffset, if any) instead of the
+/// HasCameraFocus entity, e.g. one per SplitCamera. CameraBounds and CameraDeadZone apply as usual
+#[derive(Debug, Clone, Copy, Component)]
+pub struct CameraFollow {
+    pub target: Entity,
+}
+
+/// Keeps the camera center inside this world-space rectangle when following the focus
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct CameraBounds(pub Rect);
+
+/// Half-size of a box around the camera center the focus can move in without the camera
+/// following, in world units
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct CameraDeadZone(pub Vec2);
+
+/// Where a camera at `current` should move to follow `target`, honoring the dead zone and bounds
+fn camera_follow_position(
+    current: Vec2,
+    target: Vec2,
+    bounds: &Option<Res<CameraBounds>>,
+    dead_zone: &Option<Res<CameraDeadZone>>,
+) -> Vec2 {
+    let mut center = match dead_zone {
+        // only move by however far the focus is outside the dead zone
+        Some(dead_zone) => target - (target - current).clamp(-dead_zone.0, dead_zone.0),
+        None => target,
+    };
+
+    if let Some(bounds) = bounds {
+        center = center.clamp(bounds.0.min, bounds.0.max);
+    }
+
+    center
+}
+
+pub fn snap_camera_to_focus(
+    mut query_camera: Query<&mut Transform, (With<MainCamera>, Without<CameraFollow>)>,
+    query_focus: Query<(&GlobalTransform, Option<&CameraFocusOffset>), With<HasCameraFocus>>,
+    bounds: Option<Res<CameraBounds>>,
+    dead_zone: Option<Res<CameraDeadZone>>,
+    mut had_focus: Local<bool>,
+) {
+    // with no focus (cleared, or the entity was despawned) the camera stays where it is
+    let Ok((focus, offset)) = query_focus.get_single() else {
+        if *had_focus {
+            warn!("Warning: camera focus lost, the camera stays put until SetCameraFocus");
+            *had_focus = false;
+        }
+        return;
+    };
+    *had_focus = true;
+    let offset = offset.map(|offset| offset.0).unwrap_or_default();
+    let target = focus.translation().truncate() + offset;
+
+    for mut transform in query_camera.iter_mut() {
+        let center = camera_follow_position(
+            transform.translation.truncate(),
+            target,
+            &bounds,
+            &dead_zone,
+        );
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+///
+/// follow_camera_targets: Bevy system
+///
+/// Moves every camera with a CameraFollow to its target. Cameras whose target was despawned stay put
+pub fn follow_camera_targets(
+    mut query_camera: Query<(&mut Transform, &CameraFollow)>,
+    query_target: Query<(&GlobalTransform, Option<&CameraFocusOffset>)>,
+    bounds: Option<Res<CameraBounds>>,
+    dead_zone: Option<Res<CameraDeadZone>>,
+) {
+    for (mut transform, follow) in query_camera.iter_mut() {
+        let Ok((target, offset)) = query_target.get(follow.target) else {
+            continue;
+        };
+        let offset = offset.map(|offset| offset.0).unwrap_or_default();
+        let center = camera_follow_position(
+            transform.translation.truncate(),
+            target.translation().truncate() + offset,
+            &bounds,
+            &dead_zone,
+        );
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+///
+/// update_split_viewports: Bevy system
+///
+/// Keeps each SplitCamera's viewport matching the GFXPlugin.split_screen layout as the window
+/// changes size
+pub fn update_split_viewports(
+    settings: Res<GFXSettings>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(&mut Camera, &SplitCamera)>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    let window_size = window.physical_size();
+    if window_size.x == 0 || window_size.y == 0 {
+        return; // minimized
+    }
+
+    for (mut camera, split_camera) in camera_query.iter_mut() {
+        let viewport = settings
+            .split_screen
+            .viewport(window_size, split_camera.index);
+        let unchanged = camera.viewport.as_ref().is_some_and(|current| {
+            current.physical_position == viewport.physical_position
+                && current.physical_size == viewport.physical_size
+        });
+
+        if !unchanged {
+            camera.viewport = Some(viewport);
+        }
+    }
+}
+
+///
+/// draw_camera_gizmos: Bevy system
+///
+/// Outlines CameraBounds and the CameraDeadZone around the MainCamera, if they exist
+#[cfg(debug_assertions)]
+pub fn draw_camera_gizmos(
+    mut gizmos: Gizmos,
+    query_camera: Query<&GlobalTransform, With<MainCamera>>,
+    bounds: Option<Res<CameraBounds>>,
+    dead_zone: Option<Res<CameraDeadZone>>,
+) {
+    if let Some(bounds) = bounds {
+        gizmos.rect_2d(bounds.0.center(), 0.0, bounds.0.size(), Color::YELLOW);
+    }
+
+    if let Some(dead_zone) = dead_zone {
+        for camera in query_camera.iter() {
+            gizmos.rect_2d(
+                camera.translation().truncate(),
+                0.0,
+                dead_zone.0 * 2.0,
+                Color::CYAN,
+            );
+        }
+    }
+}
+
+/// World-space rectangle the MainCamera currently sees, updated every frame after transforms
+/// propagate (so it follows zoom, window resizes and camera follow). With split-screen it's the
+/// view of player 1's camera
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct CameraView {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub center: Vec2,
+}
+
+impl CameraView {
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::from_corners(self.min, self.max)
+    }
+
+    /// Like `contains`, with the view grown by `margin` on every side
+    pub fn contains_with_margin(&self, point: Vec2, margin: f32) -> bool {
+        point.cmpge(self.min - margin).all() && point.cmple(self.max + margin).all()
+    }
+}
+
+/// Despawns the entity once its position is more than `margin` world units outside the
+/// CameraView, e.g. for bullets and debris. Only entities with this component are checked
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct DespawnWhenOffscreen {
+    pub margin: f32,
+}
+
+///
+/// despawn_offscreen: Bevy system
+///
+/// Despawns DespawnWhenOffscreen entities outside the CameraView (with split-screen, player 1's
+/// view). Does nothing until the view has been computed
+pub fn despawn_offscreen(
+    mut commands: Commands,
+    camera_view: Res<CameraView>,
+    query: Query<(Entity, &GlobalTransform, &DespawnWhenOffscreen)>,
+) {
+    if camera_view.size() == Vec2::ZERO {
+        return;
+    }
+
+    query.iter().for_each(|(entity, transform, offscreen)| {
+        if !camera_view.contains_with_margin(transform.translation().truncate(), offscreen.margin) {
+            commands.entity(entity).despawn_recursive();
+        }
+    });
+}
+
+///
+/// update_camera_view: Bevy system
+///
+/// Recomputes CameraView from the first MainCamera's position and projection area
+pub fn update_camera_view(
+    query_camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut camera_view: ResMut<CameraView>,
+) {
+    let Some((_, transform, projection)) = query_camera
+        .iter()
+        .min_by_key(|(camera, _, _)| camera.order)
+    else {
+        return;
+    };
+
+    // the projection area is relative to the camera and already includes the scale and window size
+    let center = transform.translation().truncate();
+    let next = CameraView {
+        min: center + projection.area.min,
+        max: center + projection.area.max,
+        center,
+    };
+
+    if next.ne(&*camera_view) {
+        *camera_view = next;
+    }
+}
+
+///
+/// snap_camera_to_pixels: Bevy system
+///
+/// Rounds the MainCamera's GlobalTransform to the nearest physical pixel after transforms are
+/// propagated, so the whole scene moves in whole-pixel steps. Only what's rendered is snapped: the
+/// camera's Transform (and every other entity's) keeps its exact position, so camera follow and
+/// CameraDeadZone still move smoothly underneath and gameplay positions are untouched
+pub fn snap_camera_to_pixels(
+    mut query_camera: Query<(&mut GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<GFXSettings>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    for (mut global_transform, projection) in query_camera.iter_mut() {
+        let pixels_per_unit = settings.pixels_per_unit * window.scale_factor() / projection.scale;
+        let mut transform = global_transform.compute_transform();
+        transform.translation.x =
+            (transform.translation.x * pixels_per_unit).round() / pixels_per_unit;
+        transform.translation.y =
+            (transform.translation.y * pixels_per_unit).round() / pixels_per_unit;
+        *global_transform = GlobalTransform::from(transform);
+    }
+}
+
+/// Clears the whole window (the black bars) behind the letterboxed MainCamera
+#[derive(Debug, Component)]
+pub struct LetterboxCamera {}
+
+pub fn spawn_letterbox_camera(mut commands: Commands) {
+    commands.spawn((
+        LetterboxCamera {},
+        Camera2dBundle {
+            camera: Camera {
+                order: -1,
+                clear_color: ClearColorConfig::from(Color::rgb(0.0, 0.0, 0.0)),
+                ..default()
+            },
+            ..default()
+        },
+        RenderLayers::none(), // renders nothing, only clears
+    ));
+}
+
+/// Centered viewport with the target aspect ratio that fits inside a window of the given physical size
+pub fn letterbox_viewport(window_size: UVec2, target_aspect: f32) -> Viewport {
+    let width = window_size.x as f32;
+    let height = window_size.y as f32;
+
+    let (viewport_width, viewport_height) = if width / height > target_aspect {
+        (height * target_aspect, height) // window is wider, bars on the sides
+    } else {
+        (width, width / target_aspect) // window is taller, bars on the top and bottom
+    };
+
+    Viewport {
+        physical_position: UVec2::new(
+            ((width - viewport_width) / 2.0) as u32,
+            ((height - viewport_height) / 2.0) as u32,
+        ),
+        physical_size: UVec2::new(viewport_width as u32, viewport_height as u32).max(UVec2::ONE),
+        ..default()
+    }
+}
+
+///
+/// update_letterbox: Bevy system
+///
+/// Keeps the MainCamera viewport at `GFXPlugin.target_aspect` as the window changes shape
+pub fn update_letterbox(
+    settings: Res<GFXSettings>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<&mut Camera, (With<MainCamera>, Without<SplitCamera>)>,
+) {
+    let (Some(target_aspect), Ok(window)) = (settings.target_aspect, window.get_single()) else {
+        return;
+    };
+
+    let window_size = window.physical_size();
+    if window_size.x == 0 || window_size.y == 0 {
+        return; // minimized
+    }
+
+    let viewport = letterbox_viewport(window_size, target_aspect);
+
+    for mut camera in camera_query.iter_mut() {
+        let unchanged = camera.viewport.as_ref().is_some_and(|current| {
+            current.physical_position == viewport.physical_position
+                && current.physical_size == viewport.physical_size
+        });
+
+        if !unchanged {
+            camera.viewport = Some(viewport.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn despawns_only_marked_entities_outside_the_view() {
+        let mut app = App::new();
+        app.insert_resource(CameraView {
+            min: Vec2::splat(-10.0),
+            max: Vec2::splat(10.0),
+            center: Vec2::ZERO,
+        })
+        .add_systems(Update, despawn_offscreen);
+        let offscreen = DespawnWhenOffscreen { margin: 1.0 };
+        let at = |x: f32| GlobalTransform::from_translation(Vec3::new(x, 0.0, 0.0));
+
+        let inside = app.world.spawn((at(5.0), offscreen)).id();
+        let in_margin = app.world.spawn((at(10.5), offscreen)).id();
+        let outside = app.world.spawn((at(20.0), offscreen)).id();
+        let unmarked = app.world.spawn(at(20.0)).id();
+        app.update();
+
+        assert!(app.world.get_entity(inside).is_some());
+        assert!(app.world.get_entity(in_margin).is_some());
+        assert!(app.world.get_entity(outside).is_none());
+        assert!(app.world.get_entity(unmarked).is_some());
+    }
+}
@@ -0,0 +1,52 @@
+use bevy::{asset::io::embedded::EmbeddedAssetRegistry, prelude::*};
+use std::path::{Path, PathBuf};
+
+/// Asset paths with this prefix are read from the binary instead of the assets folder
+pub const EMBEDDED_SOURCE: &str = "embedded://";
+
+/// A config and the files it references, compiled into the binary with `include_bytes!`
+///
+/// Useful on WASM, where fetching many small files is slow and can hit 404/CORS issues:
+///
+/// ```ignore
+/// GFXPlugin {
+///     #[cfg(target_arch = "wasm32")]
+///     embedded: Some(
+///         EmbeddedAssets::new(include_bytes!("../assets/graphics/config.ron"))
+///             .with_file("player.png", include_bytes!("../assets/graphics/player.png")),
+///     ),
+///     ..default()
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddedAssets {
+    pub config: &'static [u8],
+    pub files: Vec<(&'static str, &'static [u8])>, // file name as written in the config, contents
+}
+
+impl EmbeddedAssets {
+    pub fn new(config: &'static [u8]) -> Self {
+        EmbeddedAssets {
+            config,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn with_file(mut self, name: &'static str, bytes: &'static [u8]) -> Self {
+        self.files.push((name, bytes));
+        self
+    }
+
+    /// Registers every file with Bevy's embedded asset source as `embedded://{dir}/{name}`
+    ///
+    /// Must be called after the AssetPlugin (part of DefaultPlugins) is added
+    pub fn register(&self, app: &mut App, dir: &str) {
+        let registry = app.world.resource::<EmbeddedAssetRegistry>();
+
+        self.files.iter().for_each(|(name, bytes)| {
+            let asset_path = format!("{}/{}", dir, name);
+            registry.insert_asset(PathBuf::from(&asset_path), Path::new(&asset_path), *bytes);
+            info!("Embedded asset: {}", asset_path);
+        });
+    }
+}
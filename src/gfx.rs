@@ -1,33 +1,389 @@
-use crate::ron_helpers::{parse, trim_extension};
-use bevy::{prelude::*, render::camera::ScalingMode::WindowSize, window::PrimaryWindow};
+// split out of this module, kept reachable at their gfx:: paths
+pub use crate::animation_control::*;
+pub use crate::camera::*;
+pub use crate::presentation::*;
+
+use crate::embedded::{EmbeddedAssets, EMBEDDED_SOURCE};
+use crate::lighting::{LightingPlugin, LitSprite};
+use crate::palette::{PaletteSwap, PaletteSwapPlugin};
+use crate::pause::GamePaused;
+use crate::rng::GameRng;
+use crate::ron_helpers::{
+    find_config, loaded_config, parse_list_as, read_config_file, register_ron_asset,
+    trim_extension, ConfigFormat, RonAsset,
+};
+use bevy::{
+    asset::LoadState,
+    diagnostic::FrameTimeDiagnosticsPlugin,
+    prelude::*,
+    render::{
+        camera::CameraUpdateSystem,
+        texture::{ImageLoaderSettings, ImageSampler},
+    },
+    sprite::Anchor,
+    transform::TransformSystem,
+    window::{PrimaryWindow, WindowResized, WindowScaleFactorChanged},
+};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
 use std::time::Duration;
 
 pub struct GFXPlugin {
     pub snap_camera: bool, // snaps camera to the entity with HasCameraFocus (must be a single entity)
+    pub graphics_dir: String, // folder containing config.ron and the sheets, relative to the Bevy asset root
+    pub debug_preview: Option<String>, // spawns every frame of the named sheet in a labeled grid
+    pub target_aspect: Option<f32>, // locks the view to this aspect ratio (e.g. 16.0 / 9.0) with black bars
+    pub embedded: Option<EmbeddedAssets>, // reads the config and sheets from the binary instead of the assets folder
+    pub clear_color: ClearColorConfig, // camera background, ClearColorConfig::None keeps what's underneath (transparent canvas)
+    pub camera_gizmos: bool, // draws CameraBounds and CameraDeadZone with gizmos (dev builds only)
+    pub pixel_snap: bool, // draws the camera at whole-pixel positions so moving scenes don't shimmer
+    pub split_screen: SplitScreen, // spawns one camera per player instead of a single MainCamera
+    pub size_from_atlas: bool, // sizes sprites by their frame's pixel size (pixels_per_unit px = 1 unit) instead of SPRITE_SIZE
+    pub pixels_per_unit: f32, // logical pixels per game unit at a camera zoom of 1.0, also used by size_from_atlas, tilemaps and text
+    pub max_animation_delta: f32, // longest frame time (seconds) animations advance by, so a stall doesn't skip them ahead
+    pub config_files: Vec<String>, // graphics configs in graphics_dir merged in order, e.g. ["characters.ron", "ui.ron"] (empty: just config.ron)
+    pub strict_config_merge: bool, // a sheet or animation defined in two config_files is an error instead of a warning
+    pub show_fps: bool, // shows an FPS and frame time overlay in the top left corner, updated every second
+    pub msaa: Msaa,     // multisample anti-aliasing, off by default so pixel art edges stay crisp
 }
 
 impl Default for GFXPlugin {
     fn default() -> Self {
-        GFXPlugin { snap_camera: false }
+        GFXPlugin {
+            snap_camera: false,
+            graphics_dir: "graphics".to_string(),
+            debug_preview: None,
+            target_aspect: None,
+            embedded: None,
+            clear_color: ClearColorConfig::from(Color::rgb(0.0, 0.0, 0.0)),
+            camera_gizmos: false,
+            pixel_snap: false,
+            split_screen: SplitScreen::None,
+            size_from_atlas: false,
+            pixels_per_unit: PIXELS_PER_UNIT,
+            max_animation_delta: 0.1,
+            config_files: Vec::new(),
+            strict_config_merge: false,
+            show_fps: false,
+            msaa: Msaa::Off,
+        }
+    }
+}
+
+/// Builder methods, e.g. `GFXPlugin::new().snap_camera(true).target_aspect(16.0 / 9.0)`
+impl GFXPlugin {
+    pub fn new() -> Self {
+        GFXPlugin::default()
+    }
+
+    pub fn snap_camera(mut self, snap_camera: bool) -> Self {
+        self.snap_camera = snap_camera;
+        self
+    }
+
+    pub fn graphics_dir(mut self, graphics_dir: impl Into<String>) -> Self {
+        self.graphics_dir = graphics_dir.into();
+        self
+    }
+
+    pub fn debug_preview(mut self, sheet_name: impl Into<String>) -> Self {
+        self.debug_preview = Some(sheet_name.into());
+        self
+    }
+
+    pub fn target_aspect(mut self, target_aspect: f32) -> Self {
+        self.target_aspect = Some(target_aspect);
+        self
+    }
+
+    pub fn embedded(mut self, embedded: EmbeddedAssets) -> Self {
+        self.embedded = Some(embedded);
+        self
+    }
+
+    pub fn clear_color(mut self, clear_color: ClearColorConfig) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    pub fn camera_gizmos(mut self, camera_gizmos: bool) -> Self {
+        self.camera_gizmos = camera_gizmos;
+        self
+    }
+
+    pub fn pixel_snap(mut self, pixel_snap: bool) -> Self {
+        self.pixel_snap = pixel_snap;
+        self
+    }
+
+    pub fn split_screen(mut self, split_screen: SplitScreen) -> Self {
+        self.split_screen = split_screen;
+        self
+    }
+
+    pub fn size_from_atlas(mut self, size_from_atlas: bool) -> Self {
+        self.size_from_atlas = size_from_atlas;
+        self
+    }
+
+    /// Ignored with a warning unless it's finite and greater than 0
+    pub fn pixels_per_unit(mut self, pixels_per_unit: f32) -> Self {
+        if pixels_per_unit.is_finite() && pixels_per_unit > 0.0 {
+            self.pixels_per_unit = pixels_per_unit;
+        } else {
+            warn!(
+                "Warning: ignoring invalid pixels per unit {}",
+                pixels_per_unit
+            );
+        }
+        self
+    }
+
+    /// Ignored with a warning unless it's finite and not negative
+    pub fn max_animation_delta(mut self, max_animation_delta: f32) -> Self {
+        if MaxAnimationDelta::is_valid(max_animation_delta) {
+            self.max_animation_delta = max_animation_delta;
+        } else {
+            warn!(
+                "Warning: ignoring invalid max animation delta {}",
+                max_animation_delta
+            );
+        }
+        self
+    }
+
+    pub fn config_files(
+        mut self,
+        config_files: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.config_files = config_files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn strict_config_merge(mut self, strict_config_merge: bool) -> Self {
+        self.strict_config_merge = strict_config_merge;
+        self
+    }
+
+    pub fn show_fps(mut self, show_fps: bool) -> Self {
+        self.show_fps = show_fps;
+        self
+    }
+
+    pub fn msaa(mut self, msaa: Msaa) -> Self {
+        self.msaa = msaa;
+        self
     }
 }
 
 impl Plugin for GFXPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (load_sprite_sheets, spawn_camera))
-            .add_systems(
-                Update,
-                (
-                    update_animations,
-                    add_sprite_from_sprite_meta.after(update_animations),
-                    update_sprite_scaling.after(add_sprite_from_sprite_meta),
-                ),
+        if let Some(embedded) = &self.embedded {
+            embedded.register(app, &self.graphics_dir);
+        }
+
+        register_ron_asset(app);
+
+        app.insert_resource(GFXSettings {
+            graphics_dir: self.graphics_dir.clone(),
+            debug_preview: self.debug_preview.clone(),
+            target_aspect: self.target_aspect,
+            embedded_config: self.embedded.as_ref().map(|embedded| embedded.config),
+            clear_color: self.clear_color.clone(),
+            split_screen: self.split_screen,
+            size_from_atlas: self.size_from_atlas,
+            pixels_per_unit: self.pixels_per_unit,
+            config_files: self.config_files.clone(),
+            strict_config_merge: self.strict_config_merge,
+        })
+        .insert_resource(self.msaa)
+        .init_resource::<AnimationTimeScale>()
+        .insert_resource(MaxAnimationDelta::new(self.max_animation_delta))
+        .init_resource::<GamePaused>()
+        .init_resource::<GameRng>()
+        .add_event::<DespawnBySheet>()
+        .add_event::<AnimationFrameEvent>()
+        .add_event::<AnimationFinished>()
+        .add_event::<AnimationTrigger>()
+        .add_event::<SpawnEffect>()
+        .init_resource::<CameraView>()
+        .init_resource::<CameraZoomLimits>()
+        .init_resource::<AnimationCallbacks>()
+        .add_event::<ScreenFade>()
+        .add_event::<ScreenFadeComplete>()
+        .add_event::<SetCameraFocus>()
+        .add_plugins((PaletteSwapPlugin {}, LightingPlugin {}))
+        .add_systems(Startup, (load_sprite_sheets, spawn_camera))
+        .add_systems(
+            Update,
+            (
+                update_animation_controllers.before(update_animations),
+                update_directional_animations.before(update_animations),
+                update_facing_from_velocity.before(update_directional_animations),
+                update_animations,
+                spawn_sprite_layers,
+                sync_sprite_layers.after(update_animations),
+                tick_despawn_timers.after(update_animations),
+                build_pending_atlases.before(add_sprite_from_sprite_meta),
+                add_sprite_from_sprite_meta.after(update_animations),
+                update_sprite_scaling
+                    .after(add_sprite_from_sprite_meta)
+                    .run_if(
+                        on_event::<WindowResized>().or_else(on_event::<WindowScaleFactorChanged>()),
+                    ),
+                update_sprite_meta_scale.after(add_sprite_from_sprite_meta),
+                despawn_by_sheet.run_if(on_event::<DespawnBySheet>()),
+                spawn_effects.run_if(on_event::<SpawnEffect>()),
+                start_screen_fade.run_if(on_event::<ScreenFade>()),
+                update_screen_fade.after(start_screen_fade),
+            ),
+        );
+
+        app.add_systems(
+            PostUpdate,
+            (
+                update_billboards.after(TransformSystem::TransformPropagate),
+                update_camera_view
+                    .after(TransformSystem::TransformPropagate)
+                    .after(CameraUpdateSystem),
+                despawn_offscreen.after(update_camera_view),
+            ),
+        );
+
+        // camera follow and zoom run in Update, so the camera has moved by now. CameraView is
+        // computed from the snapped position, which is what's actually drawn
+        if self.pixel_snap {
+            app.add_systems(
+                PostUpdate,
+                snap_camera_to_pixels
+                    .after(TransformSystem::TransformPropagate)
+                    .before(update_camera_view),
             );
+        }
 
+        // dev builds only, the flag does nothing in a release build
+        #[cfg(debug_assertions)]
+        if self.camera_gizmos {
+            app.add_systems(Update, draw_camera_gizmos);
+        }
+
+        app.add_systems(
+            Update,
+            set_camera_focus.run_if(on_event::<SetCameraFocus>()),
+        );
         if self.snap_camera {
-            app.add_systems(Update, snap_camera_to_focus);
+            app.add_systems(Update, snap_camera_to_focus.after(set_camera_focus));
+        }
+
+        app.add_systems(Update, (follow_camera_targets, apply_camera_zoom));
+        if self.split_screen != SplitScreen::None {
+            app.add_systems(Update, update_split_viewports);
+        }
+
+        if self.target_aspect.is_some() {
+            app.add_systems(Startup, spawn_letterbox_camera)
+                .add_systems(Update, update_letterbox);
+        }
+
+        app.add_systems(
+            Update,
+            (
+                apply_mirror_scale.after(update_animations),
+                face_velocity,
+                run_animation_callbacks
+                    .after(update_animations)
+                    .run_if(on_event::<AnimationFinished>()),
+            ),
+        );
+
+        app.add_systems(
+            Update,
+            load_pending_graphics_config.run_if(resource_exists::<PendingGraphicsConfig>),
+        );
+
+        if self.debug_preview.is_some() {
+            app.add_systems(Startup, spawn_debug_preview.after(load_sprite_sheets));
+        }
+
+        // the diagnostics are only collected when the overlay is on
+        if self.show_fps {
+            if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+                app.add_plugins(FrameTimeDiagnosticsPlugin);
+            }
+            app.add_systems(Startup, spawn_fps_overlay)
+                .add_systems(Update, update_fps_overlay);
+        }
+    }
+}
+
+/// Settings copied from GFXPlugin so systems can read them
+#[derive(Debug, Clone, Resource)]
+pub struct GFXSettings {
+    /// Relative to the Bevy asset root (`assets/` by default), e.g. "graphics" or "levels/forest"
+    pub graphics_dir: String,
+    pub debug_preview: Option<String>,
+    pub target_aspect: Option<f32>,
+    pub embedded_config: Option<&'static [u8]>,
+    pub clear_color: ClearColorConfig,
+    pub split_screen: SplitScreen,
+    pub size_from_atlas: bool,
+    pub pixels_per_unit: f32,
+    pub config_files: Vec<String>,
+    pub strict_config_merge: bool,
+}
+
+impl GFXSettings {
+    /// Asset path of a file in the graphics folder (embedded or on disk)
+    pub fn asset_path(&self, file: &str) -> String {
+        if self.embedded_config.is_some() {
+            format!("{}{}/{}", EMBEDDED_SOURCE, self.graphics_dir, file)
+        } else {
+            format!("{}/{}", self.graphics_dir, file)
+        }
+    }
+
+    /// Reads and parses the graphics config (embedded or on disk). On disk it can be config.ron, or
+    /// config.json/config.toml with the matching cargo feature. Embedded configs are RON
+    ///
+    /// With config_files set, each file is read instead and they're merged with
+    /// merge_graphics_configs
+    pub fn read_config(&self) -> Result<Vec<SpriteSheetConfig>, Box<dyn Error>> {
+        match self.embedded_config {
+            Some(bytes) => parse_graphics_config(bytes, ConfigFormat::Ron)
+                .map_err(|e| format!("{}: {}", self.asset_path("config.ron"), e).into()),
+            None if self.config_files.is_empty() => {
+                let (path, format) =
+                    find_config(&format!("./assets/{}", self.graphics_dir), "config")?;
+                read_config_file(&path, format, parse_graphics_config)
+            }
+            None => {
+                let configs = self
+                    .config_files
+                    .iter()
+                    .map(|file| {
+                        let path = format!("./assets/{}/{}", self.graphics_dir, file);
+                        let format = ConfigFormat::from_path(&path)
+                            .ok_or_else(|| format!("unsupported config format: {}", file))?;
+                        let config = read_config_file(&path, format, parse_graphics_config)?;
+                        Ok((file.clone(), config))
+                    })
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                Ok(merge_graphics_configs(configs, self.strict_config_merge)?)
+            }
+        }
+    }
+
+    /// Asset paths of the graphics configs to request on WASM (RON only)
+    fn config_asset_paths(&self) -> Vec<String> {
+        if self.config_files.is_empty() {
+            vec![self.asset_path("config.ron")]
+        } else {
+            self.config_files
+                .iter()
+                .map(|file| self.asset_path(file))
+                .collect()
         }
     }
 }
@@ -35,13 +391,47 @@ impl Plugin for GFXPlugin {
 /// Important: this is the sprite size before window scaling is applied
 pub const SPRITE_SIZE: f32 = 1.0;
 
-#[derive(Debug, Clone)]
+/// Sprite custom_size for the given window scale factor and SpriteMeta scale
+pub fn sprite_size(scale_factor: f32, scale: f32) -> Vec2 {
+    Vec2::splat(SPRITE_SIZE * scale_factor * scale)
+}
+
+/// Sprite custom_size for a frame of an atlas: sprite_size, or with GFXPlugin.size_from_atlas the
+/// frame's pixel size in game units (so a 32px tile is twice the size of a 16px one)
+pub fn atlas_sprite_size(
+    settings: &GFXSettings,
+    texture_atlas_layouts: &Assets<TextureAtlasLayout>,
+    atlas: &TextureAtlas,
+    scale_factor: f32,
+    scale: f32,
+) -> Vec2 {
+    if settings.size_from_atlas {
+        let frame = texture_atlas_layouts
+            .get(&atlas.layout)
+            .and_then(|layout| layout.textures.get(atlas.index));
+        if let Some(frame) = frame {
+            return frame.size() / settings.pixels_per_unit * scale_factor * scale;
+        }
+    }
+    sprite_size(scale_factor, scale)
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct SpriteSheetHandle {
     pub texture: Handle<Image>,
     pub layout: Handle<TextureAtlasLayout>,
+    pub tile_count: usize, // number of frames in the layout (rows * columns for a grid)
+    pub normal_map: Option<Handle<Image>>, // same layout as the texture, the sheet is drawn lit if set
 }
 
-#[derive(Debug, Resource)]
+impl SpriteSheetHandle {
+    /// Number of frames in the sheet, without having to fetch the TextureAtlasLayout asset
+    pub fn frame_count(&self) -> usize {
+        self.tile_count
+    }
+}
+
+#[derive(Debug, Default, Resource)]
 pub struct SpriteSheetResource {
     map: HashMap<String, SpriteSheetHandle>,
 }
@@ -62,319 +452,2690 @@ impl SpriteSheetResource {
     pub fn get(&self, name: &str) -> Option<SpriteSheetHandle> {
         self.map.get(name).cloned()
     }
-}
-
-///
-/// load_sprite_sheets: Bevy system
-///
-/// This system scans the graphics folder for sprite sheets and loads the resources
-/// to the asset server
-pub fn load_sprite_sheets(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-) {
-    let config = parse::<
-        Vec<(
-            String,
-            f32,
-            usize,
-            usize,
-            Vec<(String, usize, usize, f32, AnimationType)>,
-        )>,
-    >("./assets/graphics/config.ron")
-    .expect("Fatal: could not parse graphics/config.ron");
 
-    let mut sprite_sheet_resource = SpriteSheetResource::new();
-    let mut animation_resource = AnimationResource::new();
+    /// Iterate over every sheet name and SpriteSheetHandle
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SpriteSheetHandle)> {
+        self.map.iter()
+    }
 
-    config
-        .iter()
-        .for_each(|(sheet_name, tile_size, rows, columns, animations)| {
-            // load sprite sheets
-            let layout = TextureAtlasLayout::from_grid(
-                Vec2::new(*tile_size, *tile_size),
-                *columns,
-                *rows,
-                None,
-                None,
+    /// Rebuilds one grid sheet from its config entry (e.g. after editing the image or its grid) and
+    /// leaves every other sheet alone. The image is reloaded from disk and the atlas layout rebuilt
+    ///
+    /// Sprites pick up the new sheet the next time they're rebuilt (SpriteAdded removed, e.g. on
+    /// their next frame change). Sprites using a SpriteMeta::from_handle handle keep the old layout.
+    /// Sheets built from individual `files` can't be rebuilt on their own
+    pub fn reload_one(
+        &mut self,
+        name: &str,
+        settings: &GFXSettings,
+        asset_server: &AssetServer,
+        texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+        config_entry: &SpriteSheetConfig,
+    ) {
+        if !config_entry.files.is_empty() {
+            warn!(
+                "Warning: can't rebuild sprite sheet {}, sheets built from files aren't supported",
+                name
             );
+            return;
+        }
 
-            let sprite_sheet_handle = SpriteSheetHandle {
-                texture: asset_server.load(&format!("graphics/{}", *sheet_name)),
-                layout: texture_atlas_layouts.add(layout),
-            };
+        // the old layout is freed once no sprite uses it anymore
+        let handle = load_grid_sheet(config_entry, settings, asset_server, texture_atlas_layouts);
+        asset_server.reload(settings.asset_path(&config_entry.file));
+        self.insert(name.to_string(), handle);
 
-            sprite_sheet_resource.insert(trim_extension(sheet_name), sprite_sheet_handle);
+        info!(
+            "Rebuilt sprite sheet: {} ({}, tile size: {}px, {} row(s), {} column(s))",
+            name,
+            config_entry.file,
+            config_entry.tile_size,
+            config_entry.rows,
+            config_entry.columns
+        );
+    }
 
-            info!(
-                "Loaded sprite sheet: {}, tile size: {}px, {} row(s), {} column(s)",
-                sheet_name, tile_size, rows, columns
-            );
+    /// Check that every frame index is within the named sheet
+    pub fn validate_frames(&self, name: &str, frames: &[usize]) -> Result<(), AnimationError> {
+        let frame_count = self
+            .map
+            .get(name)
+            .map(|handle| handle.frame_count())
+            .ok_or_else(|| AnimationError::UnknownSheet(name.to_string()))?;
 
-            // load animations
-            animations
-                .iter()
-                .for_each(|(anim_name, start, end, frame_time, animation_type)| {
-                    let animation = Animation::new(
-                        sheet_name.clone(),
-                        (*start..=*end).collect(),
-                        *frame_time,
-                        animation_type.clone(),
-                    );
-                    animation_resource.insert(anim_name.clone(), animation);
-
-                    info!("Loaded animation: {}", anim_name);
-                });
-        });
+        match frames.iter().find(|frame| **frame >= frame_count) {
+            Some(frame) => Err(AnimationError::FrameOutOfRange {
+                frame: *frame,
+                frame_count,
+            }),
+            None => Ok(()),
+        }
+    }
+}
 
-    commands.insert_resource(sprite_sheet_resource);
-    commands.insert_resource(animation_resource);
+/// One animation entry of a sprite sheet in graphics/config.ron
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AnimationConfig {
+    pub name: String,
+    #[serde(default)]
+    pub start: usize,
+    #[serde(default)]
+    pub end: usize,
+    #[serde(default)]
+    pub frames: Vec<usize>, // explicit frame indices, e.g. [0, 2, 4], used instead of start..=end when set
+    #[serde(default)]
+    pub frame_time: Option<f32>, // seconds per frame, set exactly one of frame_time, fps and frame_ms
+    #[serde(default)]
+    pub fps: Option<f32>, // frames per second, e.g. 12.0
+    #[serde(default)]
+    pub frame_ms: Option<u32>, // milliseconds per frame, e.g. 83
+    pub animation_type: AnimationType,
+    #[serde(default)]
+    pub then: Option<String>, // animation to switch to when a Once animation finishes, e.g. Some("idle")
+    #[serde(default)]
+    pub labels: HashMap<usize, String>, // frame position (0 = first frame) -> label, e.g. {3: "hitbox"}
+    #[serde(default)]
+    pub loop_range: Option<(usize, usize)>, // frame positions to hold-and-loop until released, e.g. Some((2, 4))
+    #[serde(default)]
+    pub despawn_delay: f32, // seconds a Despawn animation holds its last frame before despawning
+    #[serde(default)]
+    pub keep_on_finish: bool, // a finished Once/OnceReverse animation stays on the entity instead of being removed
+    #[serde(default)]
+    pub flip_x_frames: Vec<usize>, // frame positions drawn mirrored, for sheets that reuse mirrored poses
+    #[serde(default)]
+    pub triggers: Vec<FrameTrigger>, // e.g. [(frame: 3, event: "hitbox", data: "heavy")]
+    #[serde(default)]
+    pub on_finish_sound: Option<String>, // sound from sounds/config.ron played when a non-repeating animation ends
 }
 
-#[derive(Debug, Clone, PartialEq, Component)]
-pub struct SpriteMeta {
-    pub index: usize,
-    pub sheet_name: String,
+/// An AnimationTrigger sent when an animation enters a frame position (0 = first frame). `data` is
+/// passed through untouched for gameplay code to interpret
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FrameTrigger {
+    pub frame: usize,
+    #[serde(rename = "event")]
+    pub event_name: String,
+    #[serde(default)]
+    pub data: String,
 }
 
-impl Default for SpriteMeta {
-    fn default() -> Self {
-        SpriteMeta {
-            index: 0,
-            sheet_name: "default".to_string(),
+impl AnimationConfig {
+    /// Atlas indices of the animation: `frames` if set, otherwise `start..=end`
+    pub fn frame_indices(&self) -> Vec<usize> {
+        if self.frames.is_empty() {
+            (self.start..=self.end).collect()
+        } else {
+            self.frames.clone()
         }
     }
-}
-
-#[derive(Debug, Component)]
-pub struct SpriteAdded {}
 
-///
-/// add_sprite_from_sprite_meta: Bevy system
-///
-/// This system finds SpriteMeta components that do not have a sprite sheet bundle added yet,
-/// and adds the bundle. The SpriteMeta component contains only an identifier and an index, so
-/// this system needs to run for anything to be displayed on the screen
-pub fn add_sprite_from_sprite_meta(
-    mut commands: Commands,
-    query: Query<
-        (Entity, &SpriteMeta),
-        (
-            With<SpriteMeta>,
-            With<GlobalTransform>,
-            Without<SpriteAdded>,
-        ),
-    >,
-    sprite_sheet_resource: Res<SpriteSheetResource>,
-    window: Query<&Window, With<PrimaryWindow>>,
-) {
-    for (entity, sprite) in query.iter() {
-        let handle = sprite_sheet_resource.get(sprite.sheet_name.as_str());
+    /// Atlas indices of the animation, checked against a sheet with `frame_count` frames. A
+    /// `start..=end` range with start past end has no frames and is rejected like an empty list
+    pub fn checked_frame_indices(&self, frame_count: usize) -> Result<Vec<usize>, AnimationError> {
+        let frames = self.frame_indices();
+        if frames.is_empty() {
+            return Err(AnimationError::EmptyFrames);
+        }
 
-        if let Some(handle) = handle {
-            commands
-                .entity(entity)
-                .insert(SpriteSheetBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(
-                            SPRITE_SIZE * window.single().scale_factor(),
-                            SPRITE_SIZE * window.single().scale_factor(),
-                        )),
-                        color: Color::rgb(1.0, 1.0, 1.0), // needed for shading to work properly
-                        ..default()
-                    },
-                    texture: handle.texture.clone(),
-                    atlas: TextureAtlas {
-                        layout: handle.layout.clone(),
-                        index: sprite.index,
-                    },
-                    ..default()
-                })
-                .insert(SpriteAdded {});
-        } else {
-            warn!("Warning: no sprite sheet named {} found", sprite.sheet_name);
+        match frames.iter().find(|frame| **frame >= frame_count) {
+            Some(frame) => Err(AnimationError::FrameOutOfRange {
+                frame: *frame,
+                frame_count,
+            }),
+            None => Ok(frames),
         }
     }
-}
-
-///
-/// update_sprite_scaling: Bevy system
-///
-/// Updates sprite scaling for each Sprite if the window changes
-pub fn update_sprite_scaling(
-    mut sprites_query: Query<&mut Sprite, With<SpriteAdded>>,
-    window: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
-) {
-    sprites_query.iter_mut().for_each(|mut sprite| {
-        sprite.custom_size = Some(Vec2::new(
-            SPRITE_SIZE * window.single().scale_factor(),
-            SPRITE_SIZE * window.single().scale_factor(),
-        ));
-    });
-}
 
-///
-/// AnimationType
-///
-/// * Once: plays once and stops on the last frame
-/// * Repeat: loops indefinitely
-/// * Despawn: despawns the entity on completion
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-pub enum AnimationType {
-    Once,
-    Repeat,
-    Despawn,
+    /// Seconds per frame from whichever of `frame_time`, `fps` or `frame_ms` is set. Exactly one
+    /// must be set, and it must be greater than 0 (a zero frame time would never let the timer
+    /// settle)
+    pub fn frame_seconds(&self) -> Result<f32, AnimationError> {
+        match (self.frame_time, self.fps, self.frame_ms) {
+            (Some(frame_time), None, None) if frame_time > 0.0 && frame_time.is_finite() => {
+                Ok(frame_time)
+            }
+            (None, Some(fps), None) if fps > 0.0 && fps.is_finite() => Ok(1.0 / fps),
+            (None, None, Some(frame_ms)) if frame_ms > 0 => Ok(frame_ms as f32 / 1000.0),
+            (Some(_), None, None) | (None, Some(_), None) | (None, None, Some(_)) => {
+                Err(AnimationError::InvalidFrameTime)
+            }
+            (None, None, None) => Err(AnimationError::MissingFrameTime),
+            _ => Err(AnimationError::ConflictingFrameTime),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Component)]
-pub struct Animation {
-    index: usize,
-    sheet_name: String,
-    frames: Vec<usize>,
-    timer: Timer,
-    animation_type: AnimationType,
-    finished: bool,
+/// One sprite sheet entry in graphics/config.ron
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheetConfig {
+    pub file: String, // the sheet image, or just the sheet name when `files` is used
+    #[serde(default)]
+    pub tile_size: f32,
+    #[serde(default)]
+    pub rows: usize,
+    #[serde(default)]
+    pub columns: usize,
+    #[serde(default)]
+    pub files: Vec<String>, // individual frame images, packed into one atlas in the order listed
+    #[serde(default)]
+    pub filter: SamplerMode,
+    #[serde(default)]
+    pub row_segments: Vec<(usize, usize)>, // rows to use (inclusive), e.g. [(0, 0), (2, 3)] skips row 1
+    #[serde(default)]
+    pub normal_map: Option<String>, // normal map image laid out like `file`, grid sheets only
+    #[serde(default)]
+    pub animations: Vec<AnimationConfig>,
 }
 
-impl Animation {
-    pub fn new(
-        sheet_name: String,
-        frames: Vec<usize>,
-        frame_time: f32,
-        animation_type: AnimationType,
-    ) -> Self {
-        Animation {
-            index: 0,
-            sheet_name,
-            frames,
-            timer: Timer::from_seconds(frame_time, TimerMode::Once),
-            animation_type,
-            finished: false,
+impl SpriteSheetConfig {
+    /// Grid rows that hold frames: every row, or only the rows in `row_segments` if set
+    pub fn frame_rows(&self) -> Vec<usize> {
+        if self.row_segments.is_empty() {
+            (0..self.rows).collect()
+        } else {
+            self.row_segments
+                .iter()
+                .flat_map(|(start, end)| *start..=(*end).min(self.rows.saturating_sub(1)))
+                .collect()
         }
     }
 
-    fn advance_frame(&mut self) {
-        if self.animation_type.eq(&AnimationType::Repeat) {
-            self.index = (self.index + 1) % self.frames.len();
-            self.timer.reset();
-            return;
-        }
-
-        // non-repeating animation
-        if self.index < self.frames.len() - 1 {
-            self.index += 1;
-            self.timer.reset();
+    /// Number of frames in the sheet, which animation frame indices count up to
+    pub fn frame_count(&self) -> usize {
+        if self.files.is_empty() {
+            self.frame_rows().len() * self.columns
         } else {
-            self.finished = true;
+            self.files.len()
         }
     }
+}
 
-    /// Advances the timer and returns the index of the current frame
-    pub fn tick(&mut self, delta: f32) -> usize {
-        self.timer.tick(Duration::from_secs_f32(delta));
-        if self.timer.finished() {
-            self.advance_frame();
+/// How a sheet's texture is sampled when scaled. Nearest keeps pixel art crisp, Linear smooths it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum SamplerMode {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+impl SamplerMode {
+    pub fn sampler(&self) -> ImageSampler {
+        match self {
+            SamplerMode::Nearest => ImageSampler::nearest(),
+            SamplerMode::Linear => ImageSampler::linear(),
         }
-        self.frames[self.index].clone()
     }
+}
 
-    pub fn sheet_name(&self) -> &str {
-        self.sheet_name.as_str()
-    }
+/// The original tuple format: (filename, tile size, rows, columns, [(name, start, end, frame time, type)])
+type LegacySpriteSheetConfig = (
+    String,
+    f32,
+    usize,
+    usize,
+    Vec<(String, usize, usize, f32, AnimationType)>,
+);
 
-    pub fn get_type(&self) -> AnimationType {
-        self.animation_type.clone()
+impl From<LegacySpriteSheetConfig> for SpriteSheetConfig {
+    fn from((file, tile_size, rows, columns, animations): LegacySpriteSheetConfig) -> Self {
+        SpriteSheetConfig {
+            file,
+            tile_size,
+            rows,
+            columns,
+            files: Vec::new(),
+            filter: SamplerMode::default(),
+            row_segments: Vec::new(),
+            normal_map: None,
+            animations: animations
+                .into_iter()
+                .map(
+                    |(name, start, end, frame_time, animation_type)| AnimationConfig {
+                        name,
+                        start,
+                        end,
+                        frame_time: Some(frame_time),
+                        animation_type,
+                        ..default()
+                    },
+                )
+                .collect(),
+        }
     }
+}
 
-    pub fn finished(&self) -> bool {
-        self.finished
-    }
+/// Parses a graphics config, accepting both the struct format and the original tuple format
+pub fn parse_graphics_config(
+    bytes: &[u8],
+    format: ConfigFormat,
+) -> Result<Vec<SpriteSheetConfig>, Box<dyn Error>> {
+    parse_list_as::<SpriteSheetConfig>(bytes, format).or_else(|e| {
+        parse_list_as::<LegacySpriteSheetConfig>(bytes, format)
+            .map(|legacy| legacy.into_iter().map(SpriteSheetConfig::from).collect())
+            .map_err(|_| e)
+    })
 }
 
-#[derive(Debug, Resource)]
-pub struct AnimationResource {
-    map: HashMap<String, Animation>,
+/// A sheet or animation name defined in more than one graphics config file
+#[derive(Debug, Clone)]
+pub struct ConfigCollision {
+    pub kind: &'static str, // "sprite sheet" or "animation"
+    pub name: String,
+    pub first_file: String,
+    pub second_file: String,
 }
 
-impl AnimationResource {
-    pub fn new() -> Self {
-        Self {
-            map: HashMap::new(),
-        }
+impl fmt::Display for ConfigCollision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} is defined in both {} and {}",
+            self.kind, self.name, self.first_file, self.second_file
+        )
     }
+}
 
-    /// Insert a new Animation
-    pub fn insert(&mut self, name: String, animation: Animation) {
-        self.map.insert(name, animation.clone());
-    }
+impl Error for ConfigCollision {}
 
-    /// Get an Animation
-    pub fn get(&self, name: &str) -> Option<Animation> {
+/// Merges several parsed graphics configs, given as (file name, config) in load order, into one.
+/// When a later file defines a sheet or animation that an earlier file already has, the later one
+/// wins with a warning, or it's an error if strict is set. Duplicates within one file are left as
+/// they are (the last one wins when the resources are built, as with a single config)
+pub fn merge_graphics_configs(
+    configs: Vec<(String, Vec<SpriteSheetConfig>)>,
+    strict: bool,
+) -> Result<Vec<SpriteSheetConfig>, ConfigCollision> {
+    let mut merged: Vec<(String, SpriteSheetConfig)> = Vec::new();
+
+    for (file, sheets) in configs {
+        for sheet in sheets {
+            let sheet_name = trim_extension(&sheet.file);
+
+            let earlier = merged.iter().position(|(earlier_file, earlier)| {
+                *earlier_file != file && trim_extension(&earlier.file) == sheet_name
+            });
+            if let Some(position) = earlier {
+                let collision = ConfigCollision {
+                    kind: "sprite sheet",
+                    name: sheet_name.clone(),
+                    first_file: merged[position].0.clone(),
+                    second_file: file.clone(),
+                };
+                if strict {
+                    return Err(collision);
+                }
+                warn!("Warning: {}, using the one from {}", collision, file);
+                merged.remove(position);
+            }
+
+            for anim in sheet.animations.iter() {
+                for (earlier_file, earlier) in merged.iter_mut() {
+                    if *earlier_file == file
+                        || !earlier.animations.iter().any(|a| a.name == anim.name)
+                    {
+                        continue;
+                    }
+                    let collision = ConfigCollision {
+                        kind: "animation",
+                        name: anim.name.clone(),
+                        first_file: earlier_file.clone(),
+                        second_file: file.clone(),
+                    };
+                    if strict {
+                        return Err(collision);
+                    }
+                    warn!("Warning: {}, using the one from {}", collision, file);
+                    earlier.animations.retain(|a| a.name != anim.name);
+                }
+            }
+
+            merged.push((file.clone(), sheet));
+        }
+    }
+
+    Ok(merged.into_iter().map(|(_, sheet)| sheet).collect())
+}
+
+///
+/// load_sprite_sheets: Bevy system
+///
+/// This system scans the graphics folder for sprite sheets and loads the resources
+/// to the asset server. The folder is set by `GFXPlugin.graphics_dir`
+///
+/// On WASM there's no filesystem, so unless the config is embedded it's requested from the asset
+/// server instead and the resources are built by load_pending_graphics_config once it arrives (they
+/// start out empty). With GFXPlugin.config_files every file is requested and merged
+pub fn load_sprite_sheets(
+    mut commands: Commands,
+    settings: Res<GFXSettings>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    if cfg!(target_arch = "wasm32") && settings.embedded_config.is_none() {
+        commands.init_resource::<SpriteSheetResource>();
+        commands.init_resource::<AnimationResource>();
+        commands.init_resource::<PendingAtlases>();
+        commands.insert_resource(PendingGraphicsConfig(
+            settings
+                .config_asset_paths()
+                .into_iter()
+                .map(|path| asset_server.load(path))
+                .collect(),
+        ));
+        return;
+    }
+
+    let config = settings
+        .read_config()
+        .unwrap_or_else(|e| panic!("Fatal: could not load the graphics config: {}", e));
+
+    build_sprite_sheets(
+        &mut commands,
+        &settings,
+        &asset_server,
+        &mut texture_atlas_layouts,
+        &config,
+    );
+}
+
+/// Graphics config files requested from the asset server, on WASM, in merge order
+#[derive(Debug, Resource)]
+pub struct PendingGraphicsConfig(pub Vec<Handle<RonAsset>>);
+
+///
+/// load_pending_graphics_config: Bevy system
+///
+/// Builds the sprite sheet and animation resources once every PendingGraphicsConfig file has loaded
+pub fn load_pending_graphics_config(
+    mut commands: Commands,
+    settings: Res<GFXSettings>,
+    asset_server: Res<AssetServer>,
+    ron_assets: Res<Assets<RonAsset>>,
+    pending: Res<PendingGraphicsConfig>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let Some(loaded) = pending
+        .0
+        .iter()
+        .map(|handle| loaded_config(&asset_server, &ron_assets, handle))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return;
+    };
+
+    let parsed = pending
+        .0
+        .iter()
+        .zip(loaded)
+        .map(|(handle, bytes)| {
+            let file = handle
+                .path()
+                .map(|path| path.to_string())
+                .unwrap_or_default();
+            bytes
+                .and_then(|bytes| parse_graphics_config(&bytes, ConfigFormat::Ron))
+                .map_err(|e| Box::<dyn Error>::from(format!("{}: {}", file, e)))
+                .map(|config| (file, config))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()
+        .and_then(|mut configs| {
+            if configs.len() == 1 {
+                return Ok(configs.remove(0).1);
+            }
+            Ok(merge_graphics_configs(
+                configs,
+                settings.strict_config_merge,
+            )?)
+        });
+    let config =
+        parsed.unwrap_or_else(|e| panic!("Fatal: could not load the graphics config: {}", e));
+
+    build_sprite_sheets(
+        &mut commands,
+        &settings,
+        &asset_server,
+        &mut texture_atlas_layouts,
+        &config,
+    );
+    commands.remove_resource::<PendingGraphicsConfig>();
+}
+
+/// Loads the texture of a grid sheet and adds its atlas layout
+fn load_grid_sheet(
+    sheet: &SpriteSheetConfig,
+    settings: &GFXSettings,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) -> SpriteSheetHandle {
+    let tile = Vec2::new(sheet.tile_size, sheet.tile_size);
+    let layout = if sheet.row_segments.is_empty() {
+        TextureAtlasLayout::from_grid(tile, sheet.columns, sheet.rows, None, None)
+    } else {
+        // only the listed rows, numbered left to right then top to bottom as if they were adjacent
+        let mut layout = TextureAtlasLayout::new_empty(
+            tile * Vec2::new(sheet.columns as f32, sheet.rows as f32),
+        );
+        for row in sheet.frame_rows() {
+            for column in 0..sheet.columns {
+                let min = tile * Vec2::new(column as f32, row as f32);
+                layout.add_texture(Rect::from_corners(min, min + tile));
+            }
+        }
+        layout
+    };
+
+    let sampler = sheet.filter.sampler();
+    let normal_sampler = sampler.clone();
+    SpriteSheetHandle {
+        texture: asset_server.load_with_settings(
+            settings.asset_path(&sheet.file),
+            move |image_settings: &mut ImageLoaderSettings| {
+                image_settings.sampler = sampler.clone();
+            },
+        ),
+        tile_count: layout.len(),
+        layout: texture_atlas_layouts.add(layout),
+        // normals are data, not colors, so they're loaded without the sRGB conversion
+        normal_map: sheet.normal_map.as_ref().map(|normal_map| {
+            asset_server.load_with_settings(
+                settings.asset_path(normal_map),
+                move |image_settings: &mut ImageLoaderSettings| {
+                    image_settings.sampler = normal_sampler.clone();
+                    image_settings.is_srgb = false;
+                },
+            )
+        }),
+    }
+}
+
+/// Loads every sheet and animation in a parsed graphics config, replacing SpriteSheetResource,
+/// AnimationResource and PendingAtlases
+fn build_sprite_sheets(
+    commands: &mut Commands,
+    settings: &GFXSettings,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+    config: &[SpriteSheetConfig],
+) {
+    let mut sprite_sheet_resource = SpriteSheetResource::new();
+    let mut animation_resource = AnimationResource::new();
+    let mut pending_atlases = PendingAtlases::new();
+
+    config.iter().for_each(|sheet| {
+        let sheet_name = trim_extension(&sheet.file);
+
+        if sheet.files.is_empty() {
+            // load sprite sheets
+            let sprite_sheet_handle =
+                load_grid_sheet(sheet, settings, asset_server, texture_atlas_layouts);
+            sprite_sheet_resource.insert(sheet_name.clone(), sprite_sheet_handle);
+
+            info!(
+                "Loaded sprite sheet: {}, tile size: {}px, {} row(s), {} column(s)",
+                sheet.file, sheet.tile_size, sheet.rows, sheet.columns
+            );
+        } else {
+            // the atlas is built by build_pending_atlases once every frame image is loaded
+            let frames = sheet
+                .files
+                .iter()
+                .map(|file| asset_server.load(settings.asset_path(file)))
+                .collect();
+
+            pending_atlases.insert(sheet_name.clone(), frames, sheet.filter);
+
+            info!(
+                "Loading sprite sheet: {} from {} image(s)",
+                sheet_name,
+                sheet.files.len()
+            );
+        }
+
+        let frame_count = sheet.frame_count();
+
+        // load animations
+        sheet.animations.iter().for_each(|anim| {
+            let frames = match anim.checked_frame_indices(frame_count) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    warn!("Warning: skipping animation {}: {}", anim.name, e);
+                    animation_resource.mark_skipped(anim.name.clone());
+                    return;
+                }
+            };
+
+            let frame_time = match anim.frame_seconds() {
+                Ok(frame_time) => frame_time,
+                Err(e) => {
+                    warn!("Warning: skipping animation {}: {}", anim.name, e);
+                    animation_resource.mark_skipped(anim.name.clone());
+                    return;
+                }
+            };
+
+            let mut animation = Animation::new(
+                sheet_name.clone(),
+                frames,
+                frame_time,
+                anim.animation_type.clone(),
+            );
+            if let Some(then) = &anim.then {
+                animation = animation.with_then(then.clone());
+            }
+            for (position, label) in anim.labels.iter() {
+                animation = animation.with_label(*position, label.clone());
+            }
+            if let Some((loop_start, loop_end)) = anim.loop_range {
+                animation = animation.with_loop_range(loop_start, loop_end);
+            }
+            if let Some(sound) = &anim.on_finish_sound {
+                animation = animation.with_finish_sound(sound.clone());
+            }
+            for trigger in anim.triggers.iter() {
+                animation = animation.with_trigger(trigger.clone());
+            }
+            for position in anim.flip_x_frames.iter() {
+                animation = animation.with_flipped_frame(*position);
+            }
+            if anim.keep_on_finish {
+                animation = animation.with_keep_on_finish();
+            }
+            if anim.despawn_delay > 0.0 {
+                animation = animation.with_despawn_delay(anim.despawn_delay);
+            }
+            animation_resource.insert(anim.name.clone(), animation);
+
+            info!("Loaded animation: {}", anim.name);
+        });
+    });
+
+    if config.is_empty() {
+        // fine early in development, but nothing will render until a sheet is added
+        info!(
+            "0 sprite sheets loaded, the graphics config in {} is empty",
+            settings.graphics_dir
+        );
+    } else {
+        info!(
+            "{} sprite sheet(s) and {} animation(s) loaded",
+            config.len(),
+            config
+                .iter()
+                .map(|sheet| sheet.animations.len())
+                .sum::<usize>()
+        );
+    }
+
+    commands.insert_resource(sprite_sheet_resource);
+    commands.insert_resource(animation_resource);
+    commands.insert_resource(pending_atlases);
+}
+
+/// Sprite sheets made of individual frame images that are still loading
+#[derive(Debug, Default, Resource)]
+pub struct PendingAtlases {
+    map: HashMap<String, Vec<Handle<Image>>>,
+    filters: HashMap<String, SamplerMode>,
+}
+
+impl PendingAtlases {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            filters: HashMap::new(),
+        }
+    }
+
+    /// Insert the frame images of a sheet, in atlas order, and the filter for the packed texture
+    pub fn insert(&mut self, name: String, frames: Vec<Handle<Image>>, filter: SamplerMode) {
+        self.filters.insert(name.clone(), filter);
+        self.map.insert(name, frames);
+    }
+
+    /// Filter the packed texture of a sheet should use
+    pub fn filter(&self, name: &str) -> SamplerMode {
+        self.filters.get(name).copied().unwrap_or_default()
+    }
+
+    /// Iterate over every pending sheet name and its frame images
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Vec<Handle<Image>>)> {
+        self.map.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+///
+/// build_pending_atlases: Bevy system
+///
+/// Packs the frame images of each pending sheet into a single texture with TextureAtlasBuilder once
+/// they have all loaded, and adds the result to SpriteSheetResource. Atlas indices follow the order
+/// the files are listed in the config
+pub fn build_pending_atlases(
+    asset_server: Res<AssetServer>,
+    mut pending_atlases: ResMut<PendingAtlases>,
+    mut sprite_sheet_resource: ResMut<SpriteSheetResource>,
+    mut images: ResMut<Assets<Image>>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    if pending_atlases.is_empty() {
+        return;
+    }
+
+    let mut finished = Vec::new();
+
+    for (name, frames) in pending_atlases.iter() {
+        if frames
+            .iter()
+            .any(|frame| asset_server.get_load_state(frame) == Some(LoadState::Failed))
+        {
+            warn!(
+                "Warning: could not load every image of sprite sheet {}",
+                name
+            );
+            finished.push(name.clone());
+            continue;
+        }
+
+        if frames.iter().any(|frame| images.get(frame).is_none()) {
+            continue; // still loading
+        }
+
+        let mut builder = TextureAtlasBuilder::default();
+        for frame in frames.iter() {
+            builder.add_texture(Some(frame.id()), images.get(frame).unwrap());
+        }
+
+        match builder.finish() {
+            Ok((mut layout, mut texture)) => {
+                texture.sampler = pending_atlases.filter(name).sampler();
+
+                // the packer may reorder frames, put them back in config order
+                layout.textures = frames
+                    .iter()
+                    .filter_map(|frame| layout.get_texture_index(frame))
+                    .map(|index| layout.textures[index])
+                    .collect();
+                layout.texture_handles = None;
+
+                sprite_sheet_resource.insert(
+                    name.clone(),
+                    SpriteSheetHandle {
+                        texture: images.add(texture),
+                        tile_count: layout.len(),
+                        layout: texture_atlas_layouts.add(layout),
+                        normal_map: None,
+                    },
+                );
+
+                info!(
+                    "Loaded sprite sheet: {}, {} frame(s) from individual images",
+                    name,
+                    frames.len()
+                );
+            }
+            Err(e) => warn!("Warning: could not build sprite sheet {}: {:?}", name, e),
+        }
+
+        finished.push(name.clone());
+    }
+
+    finished.iter().for_each(|name| {
+        pending_atlases.map.remove(name);
+        pending_atlases.filters.remove(name);
+    });
+}
+
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct SpriteMeta {
+    pub index: usize,
+    pub sheet_name: String,
+    pub scale: f32, // multiplies SPRITE_SIZE, e.g. 2.0 for a boss or 0.5 for a pickup
+    pub handle: Option<SpriteSheetHandle>, // used instead of looking up sheet_name in SpriteSheetResource
+    pub anchor: Anchor, // point of the sprite placed at the entity position, e.g. Anchor::BottomCenter for feet
+    pub flip_x: bool,   // mirrors the sprite horizontally
+    pub frame_flip_x: bool, // set by the animation for mirrored frames, XORed with flip_x
+    pub mirror_mode: MirrorMode, // how flip_x is applied
+}
+
+///
+/// MirrorMode
+///
+/// How SpriteMeta.flip_x mirrors an entity
+///
+/// * SpriteFlip: flips only the sprite's texture (default). Children, offsets and colliders are
+///   unaffected
+/// * TransformScale: negates Transform.scale.x, so children (weapons, particle emitters, layers)
+///   and their offsets mirror with the entity. Anything reading the transform sees the negative
+///   scale too, e.g. physics colliders get mirrored and some engines reject negative scales.
+///   `frame_flip_x` frames still flip the sprite only
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorMode {
+    #[default]
+    SpriteFlip,
+    TransformScale,
+}
+
+impl Default for SpriteMeta {
+    fn default() -> Self {
+        SpriteMeta {
+            index: 0,
+            sheet_name: "default".to_string(),
+            scale: 1.0,
+            handle: None,
+            anchor: Anchor::Center,
+            flip_x: false,
+            frame_flip_x: false,
+            mirror_mode: MirrorMode::SpriteFlip,
+        }
+    }
+}
+
+impl SpriteMeta {
+    /// SpriteMeta that carries its sheet handle, skipping the SpriteSheetResource lookup. Useful
+    /// when spawning thousands of entities from the same sheet: get the handle once and clone it
+    pub fn from_handle(
+        sheet_name: impl Into<String>,
+        handle: SpriteSheetHandle,
+        index: usize,
+    ) -> Self {
+        SpriteMeta {
+            index,
+            sheet_name: sheet_name.into(),
+            handle: Some(handle),
+            ..default()
+        }
+    }
+
+    /// Sprite.flip_x for this SpriteMeta, flip_x only counts in SpriteFlip mode
+    pub fn sprite_flip_x(&self) -> bool {
+        match self.mirror_mode {
+            MirrorMode::SpriteFlip => self.flip_x ^ self.frame_flip_x,
+            MirrorMode::TransformScale => self.frame_flip_x,
+        }
+    }
+
+    /// Switches to another sheet by name, dropping the stored handle if it was for a different sheet
+    pub fn set_sheet_name(&mut self, sheet_name: &str) {
+        if self.sheet_name.ne(sheet_name) {
+            self.sheet_name = sheet_name.to_string();
+            self.handle = None;
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct SpriteAdded {}
+
+/// How long (real time, in seconds) an entity waits for its sprite sheet before giving up
+pub const SPRITE_PENDING_TIMEOUT: f32 = 5.0;
+
+/// Marks an entity whose sprite sheet wasn't found yet (e.g. still loading)
+///
+/// The sheet is looked up again every frame until it exists or SPRITE_PENDING_TIMEOUT elapses.
+/// Changing the SpriteMeta starts the wait over. The first wait for each sheet name is logged as a
+/// warning, so a typo shows up right away without every entity using it repeating it
+#[derive(Debug, Default, Component)]
+pub struct SpritePending {
+    pub elapsed: f32,
+    pub gave_up: bool,
+}
+
+///
+/// add_sprite_from_sprite_meta: Bevy system
+///
+/// This system finds SpriteMeta components that do not have a sprite sheet bundle added yet,
+/// and adds the bundle. The SpriteMeta component contains only an identifier and an index, so
+/// this system needs to run for anything to be displayed on the screen
+pub fn add_sprite_from_sprite_meta(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            Ref<SpriteMeta>,
+            Option<&mut SpritePending>,
+            Has<Visibility>,
+        ),
+        (
+            With<SpriteMeta>,
+            With<GlobalTransform>,
+            Without<SpriteAdded>,
+            Without<PaletteSwap>, // drawn by add_palette_swap_sprites instead
+            Without<LitSprite>,   // drawn by add_lit_sprites instead
+        ),
+    >,
+    sprite_sheet_resource: Res<SpriteSheetResource>,
+    settings: Res<GFXSettings>,
+    texture_atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    time: Res<Time<Real>>,
+    mut missing_sheets: Local<HashSet<String>>,
+) {
+    // no window yet, or it's closing
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    for (entity, sprite, pending, has_visibility) in query.iter_mut() {
+        if let Some(pending) = pending.as_ref() {
+            if pending.gave_up && !sprite.is_changed() {
+                continue;
+            }
+        }
+
+        let handle = match &sprite.handle {
+            Some(handle) => Some(handle.clone()),
+            None => sprite_sheet_resource.get(sprite.sheet_name.as_str()),
+        };
+
+        if let Some(handle) = handle {
+            // sheets with a normal map are drawn by add_lit_sprites
+            if handle.normal_map.is_some() {
+                commands.entity(entity).insert(LitSprite {});
+                if pending.is_some() {
+                    commands.entity(entity).remove::<SpritePending>();
+                }
+                continue;
+            }
+
+            let atlas = TextureAtlas {
+                layout: handle.layout.clone(),
+                index: sprite.index,
+            };
+            let size = atlas_sprite_size(
+                &settings,
+                &texture_atlas_layouts,
+                &atlas,
+                window.scale_factor(),
+                sprite.scale,
+            );
+
+            // the parts of SpriteSheetBundle that don't reset the entity's transform
+            commands
+                .entity(entity)
+                .insert((
+                    Sprite {
+                        custom_size: Some(size),
+                        color: Color::rgb(1.0, 1.0, 1.0), // needed for shading to work properly
+                        anchor: sprite.anchor,
+                        flip_x: sprite.sprite_flip_x(),
+                        ..default()
+                    },
+                    handle.texture.clone(),
+                    atlas,
+                ))
+                .insert(SpriteAdded {});
+
+            if !has_visibility {
+                commands.entity(entity).insert(VisibilityBundle::default());
+            }
+
+            if pending.is_some() {
+                commands.entity(entity).remove::<SpritePending>();
+            }
+        } else if let Some(mut pending) = pending {
+            if sprite.is_changed() {
+                *pending = SpritePending::default();
+            }
+
+            pending.elapsed += time.delta_seconds();
+            if pending.elapsed >= SPRITE_PENDING_TIMEOUT {
+                warn!(
+                    "Warning: no sprite sheet named {} found after {}s, giving up",
+                    sprite.sheet_name, SPRITE_PENDING_TIMEOUT
+                );
+                pending.gave_up = true;
+            }
+        } else {
+            if missing_sheets.insert(sprite.sheet_name.clone()) {
+                warn!(
+                    "Warning: no sprite sheet named {} found yet, waiting",
+                    sprite.sheet_name
+                );
+            }
+            commands.entity(entity).insert(SpritePending::default());
+        }
+    }
+}
+
+/// Z distance between stacked SpriteLayers
+pub const SPRITE_LAYER_Z_STEP: f32 = 0.01;
+
+///
+/// SpriteLayers
+///
+/// Extra sprites drawn on top of the entity's own, e.g. armor and a weapon over a paper-doll body.
+/// Each layer becomes a child entity (with a SpriteLayer marker) stacked SPRITE_LAYER_Z_STEP apart
+/// in list order, and shows the parent's current frame index from its own sheet, so the layers
+/// animate in sync with the parent's Animation
+///
+/// Each layer has its own SpriteMeta and goes through add_sprite_from_sprite_meta like any sprite:
+/// when the parent's frame changes, a layer's index is updated and its SpriteAdded removed so the
+/// sprite is refreshed. Layers are spawned once, when SpriteLayers is added
+#[derive(Debug, Clone, Default, Component)]
+pub struct SpriteLayers(pub Vec<SpriteMeta>);
+
+/// A child entity spawned for SpriteLayers
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SpriteLayer {
+    pub index: usize, // position in the parent's SpriteLayers
+}
+
+pub fn spawn_sprite_layers(
+    mut commands: Commands,
+    query: Query<(Entity, &SpriteLayers, &SpriteMeta), Added<SpriteLayers>>,
+) {
+    for (entity, layers, parent_meta) in query.iter() {
+        commands.entity(entity).with_children(|parent| {
+            for (index, layer) in layers.0.iter().enumerate() {
+                parent.spawn((
+                    SpriteLayer { index },
+                    SpriteMeta {
+                        index: parent_meta.index,
+                        frame_flip_x: parent_meta.frame_flip_x,
+                        ..layer.clone()
+                    },
+                    SpatialBundle::from_transform(Transform::from_xyz(
+                        0.0,
+                        0.0,
+                        SPRITE_LAYER_Z_STEP * (index + 1) as f32,
+                    )),
+                ));
+            }
+        });
+    }
+}
+
+///
+/// sync_sprite_layers: Bevy system
+///
+/// Copies the parent's frame index (and per-frame flip) to its SpriteLayer children when the
+/// parent's SpriteMeta changes
+pub fn sync_sprite_layers(
+    mut commands: Commands,
+    parent_query: Query<
+        (&SpriteMeta, &Children),
+        (
+            With<SpriteLayers>,
+            Without<SpriteLayer>,
+            Changed<SpriteMeta>,
+        ),
+    >,
+    mut layer_query: Query<&mut SpriteMeta, With<SpriteLayer>>,
+) {
+    for (parent_meta, children) in parent_query.iter() {
+        for child in children.iter() {
+            let Ok(mut layer_meta) = layer_query.get_mut(*child) else {
+                continue;
+            };
+
+            if layer_meta.index != parent_meta.index
+                || layer_meta.frame_flip_x != parent_meta.frame_flip_x
+            {
+                layer_meta.index = parent_meta.index;
+                layer_meta.frame_flip_x = parent_meta.frame_flip_x;
+                commands.entity(*child).remove::<SpriteAdded>();
+            }
+        }
+    }
+}
+
+///
+/// update_sprite_scaling: Bevy system
+///
+/// Updates sprite scaling for each Sprite when the window is resized or moved to a display with a
+/// different scale factor. Sprites are only touched if the factor actually changed
+pub fn update_sprite_scaling(
+    mut sprites_query: Query<(&SpriteMeta, &mut Sprite, &TextureAtlas), With<SpriteAdded>>,
+    settings: Res<GFXSettings>,
+    texture_atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut last_scale_factor: Local<Option<f32>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    if *last_scale_factor == Some(window.scale_factor()) {
+        return;
+    }
+    *last_scale_factor = Some(window.scale_factor());
+
+    sprites_query
+        .iter_mut()
+        .for_each(|(sprite_meta, mut sprite, atlas)| {
+            sprite.custom_size = Some(atlas_sprite_size(
+                &settings,
+                &texture_atlas_layouts,
+                atlas,
+                window.scale_factor(),
+                sprite_meta.scale,
+            ));
+        });
+}
+
+///
+/// apply_mirror_scale: Bevy system
+///
+/// Sets the sign of Transform.scale.x from SpriteMeta.flip_x for entities in
+/// MirrorMode::TransformScale. Entities in SpriteFlip mode are left alone
+pub fn apply_mirror_scale(mut query: Query<(&SpriteMeta, &mut Transform), Changed<SpriteMeta>>) {
+    query.iter_mut().for_each(|(sprite_meta, mut transform)| {
+        if sprite_meta.mirror_mode != MirrorMode::TransformScale {
+            return;
+        }
+
+        let sign = if sprite_meta.flip_x { -1.0 } else { 1.0 };
+        let scale_x = transform.scale.x.abs() * sign;
+        if transform.scale.x != scale_x {
+            transform.scale.x = scale_x;
+        }
+    });
+}
+
+///
+/// update_sprite_meta_scale: Bevy system
+///
+/// Resizes, re-anchors and flips live sprites when their SpriteMeta changes (e.g. a new scale)
+pub fn update_sprite_meta_scale(
+    mut sprites_query: Query<
+        (&SpriteMeta, &mut Sprite, &TextureAtlas),
+        (With<SpriteAdded>, Changed<SpriteMeta>),
+    >,
+    settings: Res<GFXSettings>,
+    texture_atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    sprites_query
+        .iter_mut()
+        .for_each(|(sprite_meta, mut sprite, atlas)| {
+            let size = atlas_sprite_size(
+                &settings,
+                &texture_atlas_layouts,
+                atlas,
+                window.scale_factor(),
+                sprite_meta.scale,
+            );
+            if sprite.custom_size != Some(size) {
+                sprite.custom_size = Some(size);
+            }
+            if sprite.anchor != sprite_meta.anchor {
+                sprite.anchor = sprite_meta.anchor;
+            }
+            let flip_x = sprite_meta.sprite_flip_x();
+            if sprite.flip_x != flip_x {
+                sprite.flip_x = flip_x;
+            }
+        });
+}
+
+/// Despawns every entity whose SpriteMeta uses the named sprite sheet
+#[derive(Event, Debug, Clone)]
+pub struct DespawnBySheet {
+    pub sheet_name: String,
+}
+
+impl DespawnBySheet {
+    pub fn new(sheet_name: impl Into<String>) -> Self {
+        DespawnBySheet {
+            sheet_name: sheet_name.into(),
+        }
+    }
+}
+
+///
+/// despawn_by_sheet: Bevy system
+///
+/// Handles DespawnBySheet events, e.g. to clear a level's sprites during a transition
+pub fn despawn_by_sheet(
+    mut commands: Commands,
+    mut events: EventReader<DespawnBySheet>,
+    query: Query<(Entity, &SpriteMeta)>,
+) {
+    for event in events.read() {
+        let mut count = 0;
+        for (entity, _) in query
+            .iter()
+            .filter(|(_, sprite_meta)| sprite_meta.sheet_name == event.sheet_name)
+        {
+            commands.entity(entity).despawn_recursive();
+            count += 1;
+        }
+
+        if count == 0 {
+            debug!(
+                "No entities using sprite sheet {} to despawn",
+                event.sheet_name
+            );
+        } else {
+            debug!(
+                "Despawned {} entities using sprite sheet {}",
+                count, event.sheet_name
+            );
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct DebugPreview {}
+
+///
+/// spawn_debug_preview: Bevy system
+///
+/// Lays out every frame of the sheet named by `GFXPlugin.debug_preview` in a grid, each labeled with
+/// its index, to check the tile size and row/column counts in config.ron
+pub fn spawn_debug_preview(
+    mut commands: Commands,
+    settings: Res<GFXSettings>,
+    sprite_sheet_resource: Res<SpriteSheetResource>,
+) {
+    let Some(sheet_name) = settings.debug_preview.as_ref() else {
+        return;
+    };
+
+    let Some(frame_count) = sprite_sheet_resource
+        .get(sheet_name)
+        .map(|handle| handle.frame_count())
+    else {
+        warn!("Debug preview: no sprite sheet named {} found", sheet_name);
+        return;
+    };
+
+    let columns = (frame_count as f32).sqrt().ceil().max(1.0) as usize;
+    let spacing = SPRITE_SIZE * 2.0;
+
+    for index in 0..frame_count {
+        let x = (index % columns) as f32 * spacing;
+        let y = -((index / columns) as f32) * spacing;
+
+        commands.spawn((
+            DebugPreview {},
+            SpriteMeta {
+                index,
+                sheet_name: sheet_name.clone(),
+                ..default()
+            },
+            SpatialBundle::from_transform(Transform::from_xyz(x, y, 0.0)),
+        ));
+
+        commands.spawn((
+            DebugPreview {},
+            Text2dBundle {
+                text: Text::from_section(
+                    index.to_string(),
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                // text is laid out in pixels, scale it down to roughly half a world unit
+                transform: Transform::from_xyz(x, y - SPRITE_SIZE * 0.8, 1.0)
+                    .with_scale(Vec3::splat(1.0 / 32.0)),
+                ..default()
+            },
+        ));
+    }
+
+    info!(
+        "Debug preview: showing {} frame(s) of {}",
+        frame_count, sheet_name
+    );
+}
+
+///
+/// AnimationType
+///
+/// * Once: plays once and stops on the last frame, or switches to its `then` animation if set.
+///   The Animation component is removed when it finishes, unless `keep_on_finish` is set
+/// * OnceReverse: plays backwards once and stops on the first frame
+/// * Repeat: loops indefinitely
+/// * Despawn: despawns the entity on completion
+/// * Ping: plays once and holds the last frame, keeping the Animation component so `finished()`
+///   stays true and gameplay decides what happens next (e.g. a death animation)
+///
+/// Every type except Repeat sends AnimationFinished when it completes
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub enum AnimationType {
+    #[default]
+    Once,
+    OnceReverse,
+    Repeat,
+    Despawn,
+    Ping,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationError {
+    EmptyFrames,
+    UnknownSheet(String),
+    FrameOutOfRange { frame: usize, frame_count: usize },
+    MissingFrameTime,
+    ConflictingFrameTime,
+    InvalidFrameTime,
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnimationError::EmptyFrames => write!(f, "animation has no frames"),
+            AnimationError::UnknownSheet(name) => write!(f, "no sprite sheet named {}", name),
+            AnimationError::FrameOutOfRange { frame, frame_count } => write!(
+                f,
+                "frame {} is out of range for a sheet with {} frame(s)",
+                frame, frame_count
+            ),
+            AnimationError::MissingFrameTime => {
+                write!(f, "animation needs one of frame_time, fps or frame_ms")
+            }
+            AnimationError::ConflictingFrameTime => {
+                write!(f, "only one of frame_time, fps and frame_ms can be set")
+            }
+            AnimationError::InvalidFrameTime => {
+                write!(f, "frame time must be greater than 0")
+            }
+        }
+    }
+}
+
+impl Error for AnimationError {}
+
+/// Frame time as a Duration, rounded to whole microseconds so times written in decimal (0.1s)
+/// add up exactly instead of drifting by the f32 error
+fn frame_duration(frame_time: f32) -> Duration {
+    Duration::from_micros(((frame_time as f64 * 1_000_000.0).round() as u64).max(1))
+}
+
+#[derive(Debug, Clone, Component)]
+pub struct Animation {
+    index: usize,
+    name: String,
+    sheet_name: String,
+    frames: Vec<usize>,
+    timer: Timer,
+    animation_type: AnimationType,
+    finished: bool,
+    then: Option<String>,
+    labels: HashMap<usize, String>,
+    loop_range: Option<(usize, usize)>,
+    released: bool,
+    started: bool,
+    entered_frames: Vec<usize>,
+    despawn_delay: f32,
+    keep_on_finish: bool,
+    flipped_frames: HashSet<usize>,
+    triggers: Vec<FrameTrigger>,
+    finish_sound: Option<String>,
+}
+
+impl Animation {
+    pub fn new(
+        sheet_name: String,
+        frames: Vec<usize>,
+        frame_time: f32,
+        animation_type: AnimationType,
+    ) -> Self {
+        let mut frames = frames;
+        if animation_type.eq(&AnimationType::OnceReverse) {
+            frames.reverse();
+        }
+
+        Animation {
+            index: 0,
+            sheet_name,
+            frames,
+            timer: Timer::new(frame_duration(frame_time), TimerMode::Repeating),
+            animation_type,
+            finished: false,
+            then: None,
+            labels: HashMap::new(),
+            loop_range: None,
+            released: false,
+            started: false,
+            entered_frames: Vec::new(),
+            despawn_delay: 0.0,
+            keep_on_finish: false,
+            flipped_frames: HashSet::new(),
+            triggers: Vec::new(),
+            finish_sound: None,
+        }
+    }
+
+    /// Plays a sound (by name in SoundResource) when the animation finishes, right before any
+    /// despawn or follow-up, e.g. a poof for a Despawn animation. Repeat animations never finish.
+    /// The name is sent with AnimationFinished and SoundPlugin plays it
+    pub fn with_finish_sound(mut self, sound: impl Into<String>) -> Self {
+        self.finish_sound = Some(sound.into());
+        self
+    }
+
+    pub fn finish_sound(&self) -> Option<&str> {
+        self.finish_sound.as_deref()
+    }
+
+    /// Sends an AnimationTrigger each time the animation enters the trigger's frame. Adding the
+    /// same trigger twice has no effect
+    pub fn with_trigger(mut self, trigger: FrameTrigger) -> Self {
+        if !self.triggers.contains(&trigger) {
+            self.triggers.push(trigger);
+        }
+        self
+    }
+
+    /// Triggers attached to a frame position
+    pub fn triggers(&self, frame_position: usize) -> impl Iterator<Item = &FrameTrigger> {
+        self.triggers
+            .iter()
+            .filter(move |trigger| trigger.frame == frame_position)
+    }
+
+    /// Loops between two frame positions (inclusive) once the animation reaches them, until
+    /// `release` is called, e.g. windup -> charging loop -> release for a charged attack
+    ///
+    /// Meant for Once/Despawn animations: after release the rest of the frames play and the
+    /// animation finishes as usual. A Repeat animation goes back to looping its whole sequence
+    pub fn with_loop_range(mut self, start: usize, end: usize) -> Self {
+        let last = self.frames.len().saturating_sub(1);
+        self.loop_range = Some((start.min(end).min(last), end.max(start).min(last)));
+        self
+    }
+
+    /// Draws a frame position (0 = first frame) mirrored horizontally, combined with SpriteMeta.flip_x
+    pub fn with_flipped_frame(mut self, frame_position: usize) -> Self {
+        self.flipped_frames.insert(frame_position);
+        self
+    }
+
+    /// True if the current frame is drawn mirrored
+    pub fn is_frame_flipped(&self) -> bool {
+        self.flipped_frames.contains(&self.index)
+    }
+
+    /// Keeps a finished Once/OnceReverse animation on the entity (holding its last frame) instead
+    /// of removing it, so it can be reversed or replayed later. Its `then` still plays if set
+    pub fn with_keep_on_finish(mut self) -> Self {
+        self.keep_on_finish = true;
+        self
+    }
+
+    /// True if the animation stays on the entity once finished and no longer needs ticking (Ping,
+    /// or a Once/OnceReverse animation with keep_on_finish and no `then`)
+    pub fn is_held(&self) -> bool {
+        self.finished
+            && match self.animation_type {
+                AnimationType::Ping => true,
+                AnimationType::Once | AnimationType::OnceReverse => {
+                    self.keep_on_finish && self.then.is_none()
+                }
+                _ => false,
+            }
+    }
+
+    /// Holds the last frame of a Despawn animation for `seconds` before the entity is despawned
+    pub fn with_despawn_delay(mut self, seconds: f32) -> Self {
+        self.despawn_delay = seconds.max(0.0);
+        self
+    }
+
+    /// Lets the animation continue past its loop range
+    pub fn release(&mut self) {
+        self.released = true;
+    }
+
+    /// True while the animation is held in its loop range
+    pub fn is_looping_range(&self) -> bool {
+        !self.released
+            && self
+                .loop_range
+                .is_some_and(|(start, end)| self.index >= start && self.index <= end)
+    }
+
+    /// Attaches a label to a frame position (0 = first frame). An AnimationFrameEvent is sent
+    /// with the label each time the animation enters that frame
+    pub fn with_label(mut self, frame_position: usize, label: impl Into<String>) -> Self {
+        self.labels.insert(frame_position, label.into());
+        self
+    }
+
+    /// Sets the animation to switch to when this one finishes (Once and OnceReverse only)
+    pub fn with_then(mut self, then: impl Into<String>) -> Self {
+        self.then = Some(then.into());
+        self
+    }
+
+    /// Build an animation from an explicit list of atlas indices, e.g. for sequences that are
+    /// assembled at runtime rather than defined in the config
+    ///
+    /// Returns an error if `frames` is empty or `frame_time` isn't finite and greater than 0. Use
+    /// `SpriteSheetResource::validate_frames` to check the indices against the sheet
+    pub fn from_frames(
+        sheet_name: impl Into<String>,
+        frames: Vec<usize>,
+        frame_time: f32,
+        animation_type: AnimationType,
+    ) -> Result<Self, AnimationError> {
+        if frames.is_empty() {
+            return Err(AnimationError::EmptyFrames);
+        }
+        if !(frame_time.is_finite() && frame_time > 0.0) {
+            return Err(AnimationError::InvalidFrameTime);
+        }
+        Ok(Animation::new(
+            sheet_name.into(),
+            frames,
+            frame_time,
+            animation_type,
+        ))
+    }
+
+    fn advance_frame(&mut self) {
+        if let Some((start, end)) = self.loop_range {
+            if !self.released && self.index == end {
+                self.index = start;
+                return;
+            }
+        }
+
+        if self.animation_type.eq(&AnimationType::Repeat) {
+            self.index = (self.index + 1) % self.frames.len();
+            return;
+        }
+
+        // non-repeating animation
+        if self.index < self.frames.len() - 1 {
+            self.index += 1;
+        } else {
+            self.finished = true;
+        }
+    }
+
+    /// Advances the timer and returns the index of the current frame
+    ///
+    /// The timer repeats and keeps any overshoot, so a delta spanning several frame times advances
+    /// several frames and the animation stays in sync with the clock after a stall
+    pub fn tick(&mut self, delta: f32) -> usize {
+        self.entered_frames.clear();
+        if self.finished {
+            return self.frames[self.index];
+        }
+
+        if !self.started {
+            self.started = true;
+            self.entered_frames.push(self.index);
+        }
+
+        // a repeating animation only needs the steps past its last whole cycle, so one long delta
+        // lands on the same frame as the same time in small steps. Anything else stops within one
+        // pass of the sequence. Bevy reports u32::MAX finishes for a zero length timer
+        self.timer.tick(Duration::from_secs_f32(delta));
+        let finishes = self.timer.times_finished_this_tick();
+        let frame_count = self.frames.len() as u32;
+        let steps = if self.animation_type.eq(&AnimationType::Repeat) && self.loop_range.is_none() {
+            finishes % frame_count
+        } else {
+            finishes.min(frame_count)
+        };
+        for _ in 0..steps {
+            self.advance_frame();
+            if self.finished {
+                break;
+            }
+            self.entered_frames.push(self.index);
+        }
+        self.frames[self.index]
+    }
+
+    /// Atlas index of the frame currently shown
+    pub fn current_frame(&self) -> usize {
+        self.frames[self.index]
+    }
+
+    /// Position of the current frame in the sequence (0 = first frame)
+    pub fn frame_position(&self) -> usize {
+        self.index
+    }
+
+    /// How far through the whole sequence the animation is, from 0.0 to 1.0, including the time
+    /// spent in the current frame. Repeating animations go back to 0.0 on every loop
+    pub fn progress(&self) -> f32 {
+        if self.finished {
+            return 1.0;
+        }
+        ((self.index as f32 + self.timer.fraction()) / self.frames.len() as f32).clamp(0.0, 1.0)
+    }
+
+    /// Jumps to a frame position with `elapsed_in_frame` seconds already spent on it, e.g. for a
+    /// timeline slider. Out-of-range values are clamped, NaN and infinite times count as 0.0. Frame
+    /// labels fire for the new frame on the next tick
+    pub fn seek(&mut self, frame_position: usize, elapsed_in_frame: f32) {
+        let frame_time = self.timer.duration().as_secs_f32();
+        let elapsed_in_frame = if elapsed_in_frame.is_finite() {
+            elapsed_in_frame
+        } else {
+            0.0
+        };
+        self.index = frame_position.min(self.frames.len() - 1);
+        self.timer.reset();
+        self.timer.set_elapsed(Duration::from_secs_f32(
+            elapsed_in_frame.clamp(0.0, frame_time),
+        ));
+        self.finished = !self.animation_type.eq(&AnimationType::Repeat)
+            && self.index == self.frames.len() - 1
+            && elapsed_in_frame >= frame_time;
+        self.started = false;
+    }
+
+    /// Jumps to the start of a frame position (clamped to the last frame)
+    pub fn set_frame(&mut self, frame_position: usize) {
+        self.seek(frame_position, 0.0);
+    }
+
+    /// Starts the animation from a frame position instead of the first frame (clamped to the last
+    /// frame), e.g. with a random offset so a crowd of props using the same animation don't all
+    /// pulse in unison
+    pub fn set_start_frame(&mut self, frame_position: usize) {
+        self.index = frame_position.min(self.frames.len() - 1);
+        self.timer.reset();
+        self.finished = false;
+        self.started = false;
+    }
+
+    /// Builder form of `set_start_frame`
+    pub fn with_offset(mut self, start_frame: usize) -> Self {
+        self.set_start_frame(start_frame);
+        self
+    }
+
+    /// Starts the animation from a random frame position, drawn from the shared GameRng (take it
+    /// with `ResMut<GameRng>`) so seeded runs spawn the same offsets
+    pub fn with_random_offset(self, rng: &mut GameRng) -> Self {
+        let start_frame = rng.index(self.frames.len());
+        self.with_offset(start_frame)
+    }
+
+    /// Seconds each frame is shown for
+    pub fn frame_time(&self) -> f32 {
+        self.timer.duration().as_secs_f32()
+    }
+
+    /// Changes how long each frame is shown for, e.g. to wind a spinner down. The current frame
+    /// keeps the same fraction of its time elapsed. This is the animation's own timing, the global
+    /// AnimationTimeScale still multiplies it. Values that aren't finite and positive are ignored
+    pub fn set_frame_time(&mut self, frame_time: f32) {
+        if !(frame_time.is_finite() && frame_time > 0.0) {
+            warn!("Warning: ignoring invalid frame time {}", frame_time);
+            return;
+        }
+
+        let fraction = self.timer.fraction();
+        self.timer.set_duration(frame_duration(frame_time));
+        self.timer
+            .set_elapsed(Duration::from_secs_f32(frame_time * fraction));
+    }
+
+    /// Frame positions entered during the last tick, in order
+    pub fn entered_frames(&self) -> &[usize] {
+        &self.entered_frames
+    }
+
+    /// Label attached to a frame position, if any
+    pub fn label(&self, frame_position: usize) -> Option<&str> {
+        self.labels.get(&frame_position).map(|label| label.as_str())
+    }
+
+    /// Name the animation was registered under in AnimationResource (empty for ad-hoc animations)
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Reverses the frame order in place and restarts from the (new) first frame, e.g. to close a
+    /// door with the animation that opened it
+    pub fn reverse(&mut self) {
+        self.frames.reverse();
+        self.index = 0;
+        self.timer.reset();
+        self.finished = false;
+        self.released = false;
+        self.started = false;
+    }
+
+    pub fn sheet_name(&self) -> &str {
+        self.sheet_name.as_str()
+    }
+
+    pub fn get_type(&self) -> AnimationType {
+        self.animation_type.clone()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn then(&self) -> Option<&str> {
+        self.then.as_deref()
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct AnimationResource {
+    map: HashMap<String, Animation>,
+    skipped: Vec<String>,
+}
+
+impl AnimationResource {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Names of every animation
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(|name| name.as_str())
+    }
+
+    /// Records an animation from the config that couldn't be loaded
+    pub fn mark_skipped(&mut self, name: String) {
+        self.skipped.push(name);
+    }
+
+    /// Animations from the config that were skipped because they were invalid
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Insert a new Animation
+    pub fn insert(&mut self, name: String, animation: Animation) {
+        let mut animation = animation;
+        animation.name = name.clone();
+        self.map.insert(name, animation);
+    }
+
+    /// Get an Animation
+    pub fn get(&self, name: &str) -> Option<Animation> {
         self.map.get(name).cloned()
     }
+
+    /// True if an animation with this name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.contains_key(name)
+    }
+
+    /// Type of a named animation. Only the AnimationType is cloned, not the whole Animation, so
+    /// this is cheap enough to call every frame (e.g. to avoid interrupting a Despawn animation)
+    pub fn get_type(&self, name: &str) -> Option<AnimationType> {
+        self.map
+            .get(name)
+            .map(|animation| animation.animation_type.clone())
+    }
+}
+
+/// Scales the delta used by update_animations, independent of the rest of the game
+///
+/// Animations are ticked with `Time<Virtual>` delta * this value, so pausing or slowing the virtual
+/// clock still affects them. E.g. for bullet-time on gameplay with UI animating at normal speed, set
+/// `Time<Virtual>` relative speed to 0.25 and this to 4.0
+///
+/// Negative values count as 0.0 (animations can't be played backwards this way, see
+/// `Animation::reverse`) and NaN or infinite values as 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct AnimationTimeScale(pub f32);
+
+impl Default for AnimationTimeScale {
+    fn default() -> Self {
+        AnimationTimeScale(1.0)
+    }
+}
+
+impl AnimationTimeScale {
+    /// The scale actually applied: never negative, and 1.0 if it isn't finite
+    pub fn get(&self) -> f32 {
+        if self.0.is_finite() {
+            self.0.max(0.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Longest frame delta (seconds, before AnimationTimeScale is applied) update_animations advances
+/// animations by. After a stall (a breakpoint, or a backgrounded browser tab) animations carry on
+/// from where they were instead of catching up all at once and finishing, or despawning, unseen.
+/// Set with `GFXPlugin.max_animation_delta`. Must be finite and not negative, other values fall
+/// back to the default 0.1
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct MaxAnimationDelta(pub f32);
+
+impl Default for MaxAnimationDelta {
+    fn default() -> Self {
+        MaxAnimationDelta(0.1)
+    }
+}
+
+impl MaxAnimationDelta {
+    /// The cap in seconds, or the default with a warning if `seconds` isn't valid
+    pub fn new(seconds: f32) -> Self {
+        if MaxAnimationDelta::is_valid(seconds) {
+            MaxAnimationDelta(seconds)
+        } else {
+            warn!(
+                "Warning: invalid max animation delta {}, using the default",
+                seconds
+            );
+            MaxAnimationDelta::default()
+        }
+    }
+
+    /// The cap actually applied, the default if the stored value isn't valid
+    pub fn get(&self) -> f32 {
+        if MaxAnimationDelta::is_valid(self.0) {
+            self.0
+        } else {
+            MaxAnimationDelta::default().0
+        }
+    }
+
+    fn is_valid(seconds: f32) -> bool {
+        seconds.is_finite() && seconds >= 0.0
+    }
+}
+
+/// Replaces an entity's Animation and immediately shows the new animation's first frame
+///
+/// The sprite is always rebuilt, even when the new animation starts on the frame already shown
+pub fn swap_animation(
+    commands: &mut Commands,
+    entity: Entity,
+    sprite_meta: &mut SpriteMeta,
+    animation: Animation,
+) {
+    sprite_meta.index = animation.current_frame();
+    sprite_meta.set_sheet_name(animation.sheet_name());
+    commands
+        .entity(entity)
+        .remove::<SpriteAdded>()
+        .insert(animation);
+}
+
+/// Sent when an animation enters a frame that has a label, so gameplay can react to it
+/// (e.g. spawn a hitbox on "hitbox") without the gfx module knowing what the label means
+#[derive(Event, Debug, Clone)]
+pub struct AnimationFrameEvent {
+    pub entity: Entity,
+    pub animation_name: String,
+    pub label: String,
+}
+
+/// Sent when an animation enters a frame with a FrameTrigger, once per entry. Like
+/// AnimationFrameEvent but with the trigger's data, e.g. `event_name: "hitbox", data: "heavy"`
+#[derive(Event, Debug, Clone)]
+pub struct AnimationTrigger {
+    pub entity: Entity,
+    pub event_name: String,
+    pub data: String,
+}
+
+/// Sent once when a non-repeating animation reaches its end, before any `then` animation or
+/// despawn is applied
+#[derive(Event, Debug, Clone)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub animation_name: String,
+    pub finish_sound: Option<String>, // the animation's finish sound, played by SoundPlugin
+}
+
+/// What should happen to a finished animation, decided by tick_entity
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinishAction {
+    /// Still playing, or finished and held (Repeat, Ping)
+    None,
+    /// Once/OnceReverse finished: play the next queued or `then` animation, or remove the Animation
+    FollowUp,
+    Despawn,
+    /// Despawn after holding the last frame for this many seconds
+    DespawnAfter(f32),
+}
+
+/// Result of advancing one entity's animation with tick_entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickOutcome {
+    pub index_changed: bool, // SpriteMeta.index moved to a new frame, the sprite needs refreshing
+    pub sheet_changed: bool, // SpriteMeta.sheet_name changed, the sprite needs refreshing
+    pub sprite_meta_changed: bool, // anything on the SpriteMeta changed (frame, sheet or flip)
+    pub just_finished: bool, // the animation finished during this tick
+    pub finish: FinishAction,
+}
+
+///
+/// tick_entity
+///
+/// Advances an animation by `delta` seconds and copies its frame and sheet to the SpriteMeta. No
+/// Bevy state is touched, so animation timing can be checked with fixed deltas. update_animations
+/// calls this and then applies the outcome (events, refreshing the sprite, follow-ups, despawns)
+pub fn tick_entity(
+    animation: &mut Animation,
+    sprite_meta: &mut SpriteMeta,
+    delta: f32,
+) -> TickOutcome {
+    let was_finished = animation.finished();
+    let next_index = animation.tick(delta);
+
+    let index_changed = next_index.ne(&sprite_meta.index);
+    if index_changed {
+        sprite_meta.index = next_index;
+    }
+
+    let sheet_changed = animation.sheet_name().ne(&sprite_meta.sheet_name);
+    if sheet_changed {
+        sprite_meta.set_sheet_name(animation.sheet_name());
+    }
+
+    let flip_changed = animation.is_frame_flipped() != sprite_meta.frame_flip_x;
+    if flip_changed {
+        sprite_meta.frame_flip_x = animation.is_frame_flipped();
+    }
+
+    let finish = if !animation.finished() {
+        FinishAction::None
+    } else {
+        match animation.get_type() {
+            AnimationType::Once | AnimationType::OnceReverse => FinishAction::FollowUp,
+            AnimationType::Despawn if animation.despawn_delay > 0.0 => {
+                FinishAction::DespawnAfter(animation.despawn_delay)
+            }
+            AnimationType::Despawn => FinishAction::Despawn,
+            _ => FinishAction::None,
+        }
+    };
+
+    TickOutcome {
+        index_changed,
+        sheet_changed,
+        sprite_meta_changed: index_changed || sheet_changed || flip_changed,
+        just_finished: animation.finished() && !was_finished,
+        finish,
+    }
 }
 
 pub fn update_animations(
     mut commands: Commands,
+    mut frame_events: EventWriter<AnimationFrameEvent>,
+    mut trigger_events: EventWriter<AnimationTrigger>,
+    mut finished_events: EventWriter<AnimationFinished>,
     time: Res<Time<Virtual>>,
-    mut query: Query<(Entity, &mut SpriteMeta, &mut Animation), With<SpriteAdded>>,
+    time_scale: Res<AnimationTimeScale>,
+    max_delta: Res<MaxAnimationDelta>,
+    paused: Res<GamePaused>,
+    animation_resource: Res<AnimationResource>,
+    mut query: Query<
+        (
+            Entity,
+            &mut SpriteMeta,
+            &mut Animation,
+            Option<&mut AnimationQueue>,
+            Has<DespawnTimer>,
+            Ref<SpriteAdded>,
+        ),
+        With<SpriteAdded>,
+    >,
 ) {
-    for (entity, mut sprite_meta, mut animation) in query.iter_mut() {
-        let next_index = animation.tick(time.delta_seconds());
-        if next_index.ne(&sprite_meta.index) {
+    if paused.is_paused() {
+        return;
+    }
+
+    for (entity, mut sprite_meta, mut animation, mut queue, despawning, sprite_added) in
+        query.iter_mut()
+    {
+        // held animations are done until reversed or replayed, skip them (and queued follow-ups)
+        if animation.is_held() {
+            continue;
+        }
+
+        // an Animation inserted over a finished one may start on the frame already shown, so the
+        // sprite is rebuilt even if the index didn't move, or it'd keep the old texture. A sprite
+        // built since the last run already shows the new Animation and isn't rebuilt twice
+        let newly_inserted = animation.is_added() && !sprite_added.is_added();
+
+        // only flag the SpriteMeta as changed when tick_entity actually changed it
+        let outcome = tick_entity(
+            &mut animation,
+            sprite_meta.bypass_change_detection(),
+            time.delta_seconds().min(max_delta.get()) * time_scale.get(),
+        );
+        if outcome.sprite_meta_changed {
+            sprite_meta.set_changed();
+        }
+
+        for position in animation.entered_frames() {
+            if let Some(label) = animation.label(*position) {
+                frame_events.send(AnimationFrameEvent {
+                    entity,
+                    animation_name: animation.name().to_string(),
+                    label: label.to_string(),
+                });
+            }
+            for trigger in animation.triggers(*position) {
+                trigger_events.send(AnimationTrigger {
+                    entity,
+                    event_name: trigger.event_name.clone(),
+                    data: trigger.data.clone(),
+                });
+            }
+        }
+        if outcome.index_changed || outcome.sheet_changed || newly_inserted {
             commands.entity(entity).remove::<SpriteAdded>();
-            sprite_meta.index = next_index;
         }
 
-        if animation.sheet_name().ne(&sprite_meta.sheet_name) {
-            sprite_meta.sheet_name = animation.sheet_name().to_string();
+        if outcome.just_finished {
+            finished_events.send(AnimationFinished {
+                entity,
+                animation_name: animation.name().to_string(),
+                finish_sound: animation.finish_sound().map(str::to_string),
+            });
         }
 
-        if animation.finished() {
-            match animation.get_type() {
-                AnimationType::Once => {
-                    commands.entity(entity).remove::<Animation>();
+        match outcome.finish {
+            FinishAction::FollowUp => {
+                if let Some(next) = queue
+                    .as_deref_mut()
+                    .and_then(|queue| queue.pop_next(&animation_resource))
+                {
+                    swap_animation(&mut commands, entity, &mut sprite_meta, next);
+                    continue;
                 }
-                AnimationType::Despawn => {
-                    commands.entity(entity).despawn();
+
+                match animation
+                    .then()
+                    .map(|name| (name, animation_resource.get(name)))
+                {
+                    Some((_, Some(next))) => {
+                        swap_animation(&mut commands, entity, &mut sprite_meta, next);
+                    }
+                    Some((name, None)) => {
+                        warn!("Warning: no animation named {} found to follow", name);
+                        commands.entity(entity).remove::<Animation>();
+                    }
+                    None if animation.keep_on_finish => {}
+                    None => {
+                        commands.entity(entity).remove::<Animation>();
+                    }
+                }
+            }
+            FinishAction::DespawnAfter(delay) => {
+                if !despawning {
+                    commands
+                        .entity(entity)
+                        .insert(DespawnTimer::from_seconds(delay));
                 }
-                _ => {}
             }
+            FinishAction::Despawn => {
+                // recursive so children (SpriteLayers and the like) go with it
+                commands.entity(entity).despawn_recursive();
+            }
+            FinishAction::None => {}
         }
     }
 }
 
-#[derive(Debug, Component)]
-pub struct MainCamera {}
+/// Despawns the entity when the timer finishes, inserted when a Despawn animation with a
+/// `despawn_delay` ends
+#[derive(Debug, Clone, Component)]
+pub struct DespawnTimer(pub Timer);
 
-#[derive(Debug, Component)]
-pub struct HasCameraFocus {}
-
-pub fn spawn_camera(mut commands: Commands) {
-    commands.spawn((
-        MainCamera {},
-        Camera2dBundle {
-            projection: OrthographicProjection {
-                near: -1000.0,
-                far: 1000.0,
-                scaling_mode: WindowSize(16.0), // 16 pixels per game unit
-                ..default()
-            },
-            camera: Camera {
-                clear_color: ClearColorConfig::from(Color::rgb(0.0, 0.0, 0.0)),
+impl DespawnTimer {
+    pub fn from_seconds(seconds: f32) -> Self {
+        DespawnTimer(Timer::from_seconds(seconds, TimerMode::Once))
+    }
+}
+
+///
+/// tick_despawn_timers: Bevy system
+///
+/// Ticks DespawnTimers on the same clock as animations (paused, scaled) and despawns the entities
+/// of finished ones, along with their children
+pub fn tick_despawn_timers(
+    mut commands: Commands,
+    time: Res<Time<Virtual>>,
+    time_scale: Res<AnimationTimeScale>,
+    paused: Res<GamePaused>,
+    mut query: Query<(Entity, &mut DespawnTimer)>,
+) {
+    if paused.is_paused() {
+        return;
+    }
+
+    for (entity, mut timer) in query.iter_mut() {
+        timer.0.tick(Duration::from_secs_f32(
+            time.delta_seconds() * time_scale.get(),
+        ));
+        if timer.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Everything an animated entity needs: SpriteMeta, the Animation and a SpatialBundle (so the
+/// GlobalTransform required by add_sprite_from_sprite_meta is present)
+#[derive(Bundle)]
+pub struct AnimatedSpriteBundle {
+    pub sprite_meta: SpriteMeta,
+    pub animation: Animation,
+    pub spatial: SpatialBundle,
+}
+
+impl AnimatedSpriteBundle {
+    pub fn new(animation: Animation, transform: Transform) -> Self {
+        AnimatedSpriteBundle {
+            sprite_meta: SpriteMeta {
+                index: animation.current_frame(),
+                sheet_name: animation.sheet_name().to_string(),
                 ..default()
             },
-            ..default()
-        },
-    ));
+            animation,
+            spatial: SpatialBundle::from_transform(transform),
+        }
+    }
+}
+
+impl AnimationResource {
+    /// AnimatedSpriteBundle for a named animation
+    pub fn bundle(&self, name: &str, transform: Transform) -> Option<AnimatedSpriteBundle> {
+        self.get(name)
+            .map(|animation| AnimatedSpriteBundle::new(animation, transform))
+    }
+}
+
+/// Spawns animated entities by animation name in one call, looking the animation up when the
+/// commands are applied
+///
+/// ```ignore
+/// fn spawn_player(mut commands: Commands) {
+///     let player = commands.spawn_animated("idle", Transform::from_xyz(0.0, 0.0, 1.0));
+/// }
+/// ```
+pub trait SpawnAnimatedExt {
+    fn spawn_animated(&mut self, animation: impl Into<String>, transform: Transform) -> Entity;
+}
+
+impl SpawnAnimatedExt for Commands<'_, '_> {
+    fn spawn_animated(&mut self, animation: impl Into<String>, transform: Transform) -> Entity {
+        let animation = animation.into();
+        let entity = self.spawn_empty().id();
+
+        self.add(move |world: &mut World| {
+            let bundle = world
+                .get_resource::<AnimationResource>()
+                .and_then(|animation_resource| animation_resource.bundle(&animation, transform));
+
+            match (bundle, world.get_entity_mut(entity)) {
+                (Some(bundle), Some(mut entity)) => {
+                    entity.insert(bundle);
+                }
+                (None, entity) => {
+                    warn!("Warning: no animation named {} found", animation);
+                    if let Some(entity) = entity {
+                        entity.despawn();
+                    }
+                }
+                _ => {}
+            }
+        });
+
+        entity
+    }
 }
 
-pub fn snap_camera_to_focus(
-    mut query_camera: Query<&mut Transform, With<MainCamera>>,
-    query_focus: Query<&GlobalTransform, With<HasCameraFocus>>,
+/// Spawns a fire-and-forget entity playing the named animation at a position, e.g. an explosion
+/// with a Despawn animation that cleans itself up
+#[derive(Event, Debug, Clone)]
+pub struct SpawnEffect {
+    pub animation: String,
+    pub position: Vec2,
+}
+
+impl SpawnEffect {
+    pub fn new(animation: impl Into<String>, position: Vec2) -> Self {
+        SpawnEffect {
+            animation: animation.into(),
+            position,
+        }
+    }
+}
+
+///
+/// spawn_effects: Bevy system
+///
+/// Handles SpawnEffect events by spawning an AnimatedSpriteBundle for each
+pub fn spawn_effects(
+    mut commands: Commands,
+    mut events: EventReader<SpawnEffect>,
+    animation_resource: Res<AnimationResource>,
 ) {
-    for mut transform in query_camera.iter_mut() {
-        transform.translation = query_focus.single().translation();
+    for event in events.read() {
+        let transform = Transform::from_translation(event.position.extend(0.0));
+        if let Some(bundle) = animation_resource.bundle(&event.animation, transform) {
+            commands.spawn(bundle);
+        } else {
+            warn!("Warning: no animation named {} found", event.animation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(count: usize) -> Vec<usize> {
+        (0..count).collect()
+    }
+
+    #[test]
+    fn tick_advances_one_frame_per_frame_time() {
+        let mut animation =
+            Animation::new("sheet".to_string(), frames(20), 0.1, AnimationType::Repeat);
+
+        animation.tick(1.0);
+
+        assert_eq!(animation.frame_position(), 10);
+        assert_eq!(animation.current_frame(), 10);
+    }
+
+    #[test]
+    fn advance_frame_wraps_repeating_animations() {
+        let mut animation =
+            Animation::new("sheet".to_string(), frames(3), 0.1, AnimationType::Repeat);
+
+        let positions: Vec<usize> = (0..4)
+            .map(|_| {
+                animation.advance_frame();
+                animation.frame_position()
+            })
+            .collect();
+
+        assert_eq!(positions, vec![1, 2, 0, 1]);
+        assert!(!animation.finished());
+    }
+
+    #[test]
+    fn advance_frame_finishes_once_animations_on_the_last_frame() {
+        let mut animation =
+            Animation::new("sheet".to_string(), frames(2), 0.1, AnimationType::Once);
+
+        animation.advance_frame();
+        assert_eq!(animation.frame_position(), 1);
+        assert!(!animation.finished());
+
+        animation.advance_frame();
+        assert_eq!(animation.frame_position(), 1);
+        assert!(animation.finished());
+    }
+
+    #[test]
+    fn parses_struct_and_legacy_graphics_configs() {
+        let config = br#"[
+            (file: "hero.png", tile_size: 16.0, rows: 1, columns: 4, animations: [
+                (name: "walk", start: 0, end: 3, fps: 10.0, animation_type: Repeat),
+            ]),
+        ]"#;
+        let sheets = parse_graphics_config(config, ConfigFormat::Ron).unwrap();
+        assert_eq!(sheets.len(), 1);
+        assert_eq!(sheets[0].frame_count(), 4);
+        assert_eq!(sheets[0].animations[0].frame_indices(), vec![0, 1, 2, 3]);
+        assert_eq!(sheets[0].animations[0].frame_seconds(), Ok(0.1));
+
+        let legacy = br#"[("hero.png", 16.0, 1, 4, [("walk", 0, 3, 0.1, Repeat)])]"#;
+        let sheets = parse_graphics_config(legacy, ConfigFormat::Ron).unwrap();
+        assert_eq!(sheets[0].file, "hero.png");
+        assert_eq!(sheets[0].animations[0].frame_seconds(), Ok(0.1));
+    }
+
+    #[test]
+    fn rejects_malformed_graphics_configs() {
+        assert!(parse_graphics_config(b"[(tile_size: 16.0)]", ConfigFormat::Ron).is_err());
+    }
+
+    #[test]
+    fn checks_animation_ranges_against_the_sheet() {
+        let anim = |start, end| AnimationConfig {
+            name: "walk".to_string(),
+            start,
+            end,
+            ..default()
+        };
+
+        assert_eq!(anim(0, 3).checked_frame_indices(4), Ok(vec![0, 1, 2, 3]));
+        assert_eq!(
+            anim(2, 5).checked_frame_indices(4),
+            Err(AnimationError::FrameOutOfRange {
+                frame: 4,
+                frame_count: 4
+            })
+        );
+        assert_eq!(
+            anim(3, 1).checked_frame_indices(4),
+            Err(AnimationError::EmptyFrames)
+        );
+    }
+
+    #[derive(Resource, Default)]
+    struct SpriteBuilds(usize);
+
+    fn count_sprite_builds(mut builds: ResMut<SpriteBuilds>, query: Query<(), Added<SpriteAdded>>) {
+        builds.0 += query.iter().count();
+    }
+
+    fn sprite(sheet_name: &str) -> SpriteMeta {
+        SpriteMeta {
+            sheet_name: sheet_name.to_string(),
+            ..default()
+        }
+    }
+
+    fn build_counting_app() -> App {
+        let mut app = crate::testing::test_app();
+        app.init_resource::<SpriteBuilds>().add_systems(
+            Update,
+            count_sprite_builds.after(crate::testing::mark_sprites_added),
+        );
+        app
+    }
+
+    #[test]
+    fn inserting_an_animation_on_the_shown_frame_rebuilds_the_sprite() {
+        let mut app = build_counting_app();
+        let entity = app.world.spawn(sprite("sheet")).id();
+        crate::testing::step(&mut app, Duration::ZERO);
+        crate::testing::step(&mut app, Duration::ZERO);
+        assert_eq!(app.world.resource::<SpriteBuilds>().0, 1);
+
+        // starts on frame 0, the one already shown
+        app.world.entity_mut(entity).insert(Animation::new(
+            "sheet".to_string(),
+            vec![0, 1],
+            0.1,
+            AnimationType::Once,
+        ));
+        crate::testing::step(&mut app, Duration::ZERO);
+
+        assert_eq!(app.world.resource::<SpriteBuilds>().0, 2);
+    }
+
+    #[test]
+    fn sprites_spawned_with_an_animation_are_built_once() {
+        // spawns between update_animations and the sprite build, like a command applied late in
+        // the frame
+        fn spawn_once(mut commands: Commands, mut spawned: Local<bool>) {
+            if !*spawned {
+                *spawned = true;
+                commands.spawn((
+                    sprite("sheet"),
+                    Animation::new("sheet".to_string(), frames(2), 0.1, AnimationType::Repeat),
+                ));
+            }
+        }
+
+        let mut app = build_counting_app();
+        app.add_systems(
+            Update,
+            spawn_once
+                .after(update_animations)
+                .before(crate::testing::mark_sprites_added),
+        );
+        crate::testing::step(&mut app, Duration::ZERO);
+        crate::testing::step(&mut app, Duration::ZERO);
+        crate::testing::step(&mut app, Duration::ZERO);
+
+        assert_eq!(app.world.resource::<SpriteBuilds>().0, 1);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_before_the_time_scale() {
+        let mut app = crate::testing::test_app();
+        app.world
+            .resource_mut::<Time<Virtual>>()
+            .set_max_delta(Duration::from_secs(60));
+        app.insert_resource(AnimationTimeScale(2.0));
+        let entity = app
+            .world
+            .spawn((
+                sprite("sheet"),
+                Animation::new("sheet".to_string(), frames(10), 0.1, AnimationType::Once),
+            ))
+            .id();
+
+        crate::testing::step(&mut app, Duration::ZERO);
+        crate::testing::step(&mut app, Duration::from_secs(10));
+
+        // 0.1s (the default max) at double speed is two frames, not the whole animation
+        let animation = app.world.get::<Animation>(entity).unwrap();
+        assert!(!animation.finished());
+        assert_eq!(animation.current_frame(), 2);
+    }
+
+    #[test]
+    fn invalid_time_scales_are_clamped() {
+        assert_eq!(AnimationTimeScale(2.0).get(), 2.0);
+        assert_eq!(AnimationTimeScale(-1.0).get(), 0.0);
+        assert_eq!(AnimationTimeScale(f32::NAN).get(), 1.0);
+        assert_eq!(AnimationTimeScale(f32::INFINITY).get(), 1.0);
+
+        let mut app = crate::testing::test_app();
+        app.insert_resource(AnimationTimeScale(-1.0));
+        let entity = app
+            .world
+            .spawn((
+                sprite("sheet"),
+                Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Repeat),
+            ))
+            .id();
+        crate::testing::step(&mut app, Duration::ZERO);
+        crate::testing::step(&mut app, Duration::from_millis(100));
+        assert_eq!(
+            app.world.get::<Animation>(entity).unwrap().frame_position(),
+            0
+        );
+
+        app.insert_resource(AnimationTimeScale(f32::NAN));
+        crate::testing::step(&mut app, Duration::from_millis(100));
+        assert_eq!(
+            app.world.get::<Animation>(entity).unwrap().frame_position(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_max_animation_deltas_fall_back_to_the_default() {
+        assert_eq!(MaxAnimationDelta::new(0.5).get(), 0.5);
+        assert_eq!(MaxAnimationDelta::new(-1.0), MaxAnimationDelta::default());
+        assert_eq!(
+            MaxAnimationDelta::new(f32::NAN),
+            MaxAnimationDelta::default()
+        );
+        assert_eq!(MaxAnimationDelta(f32::INFINITY).get(), 0.1);
+        assert_eq!(
+            GFXPlugin::new()
+                .max_animation_delta(f32::NAN)
+                .max_animation_delta,
+            0.1
+        );
+    }
+
+    #[test]
+    fn invalid_pixels_per_unit_are_ignored() {
+        assert_eq!(GFXPlugin::new().pixels_per_unit(32.0).pixels_per_unit, 32.0);
+        for invalid in [0.0, -16.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                GFXPlugin::new().pixels_per_unit(invalid).pixels_per_unit,
+                PIXELS_PER_UNIT
+            );
+        }
+    }
+
+    #[test]
+    fn tick_caps_huge_deltas_at_the_sequence_length() {
+        let mut animation =
+            Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Once);
+
+        animation.tick(1_000.0);
+
+        assert!(animation.finished());
+        assert_eq!(animation.current_frame(), 3);
+    }
+
+    #[test]
+    fn repeating_animations_land_on_the_same_frame_however_time_is_split() {
+        let mut once = Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Repeat);
+        let mut chunked = once.clone();
+
+        // 10 frame times and a half: two and a half loops
+        once.tick(1.05);
+        for _ in 0..21 {
+            chunked.tick(0.05);
+        }
+
+        assert_eq!(once.frame_position(), 2);
+        assert_eq!(chunked.frame_position(), 2);
+    }
+
+    #[test]
+    fn from_frames_rejects_invalid_frame_times() {
+        for frame_time in [0.0, -0.1, f32::NAN, f32::INFINITY] {
+            let animation =
+                Animation::from_frames("sheet", frames(2), frame_time, AnimationType::Once);
+            assert_eq!(animation.err(), Some(AnimationError::InvalidFrameTime));
+        }
+        assert_eq!(
+            Animation::from_frames("sheet", Vec::new(), 0.1, AnimationType::Once).err(),
+            Some(AnimationError::EmptyFrames)
+        );
+        assert!(Animation::from_frames("sheet", frames(2), 0.1, AnimationType::Once).is_ok());
+    }
+
+    #[test]
+    fn seek_treats_non_finite_times_as_zero() {
+        let mut animation =
+            Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Once);
+
+        for elapsed in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            animation.seek(3, elapsed);
+            assert_eq!(animation.frame_position(), 3);
+            assert!(!animation.finished());
+            assert_eq!(animation.progress(), 0.75);
+        }
+    }
+
+    #[test]
+    fn set_frame_time_ignores_non_finite_values() {
+        let mut animation =
+            Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Repeat);
+        animation.tick(0.05);
+        let (frame_time_before, progress_before) = (animation.frame_time(), animation.progress());
+
+        for frame_time in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 0.0, -1.0] {
+            animation.set_frame_time(frame_time);
+            assert_eq!(animation.frame_time(), frame_time_before);
+            assert_eq!(animation.progress(), progress_before);
+        }
+    }
+
+    #[test]
+    fn random_offsets_stay_within_the_frames() {
+        let mut rng = GameRng::from_seed(1);
+        let offsets: Vec<usize> = (0..20)
+            .map(|_| {
+                Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Repeat)
+                    .with_random_offset(&mut rng)
+                    .frame_position()
+            })
+            .collect();
+        assert!(offsets.iter().all(|offset| *offset < 4));
+        assert!(offsets.iter().any(|offset| *offset != offsets[0]));
+
+        // the same seed gives the same offsets
+        let mut rng = GameRng::from_seed(1);
+        let animation = Animation::new("sheet".to_string(), frames(4), 0.1, AnimationType::Repeat);
+        assert_eq!(
+            animation.with_random_offset(&mut rng).frame_position(),
+            offsets[0]
+        );
+    }
+
+    fn config(file: &str, ron: &str) -> (String, Vec<SpriteSheetConfig>) {
+        (
+            file.to_string(),
+            parse_graphics_config(ron.as_bytes(), ConfigFormat::Ron).unwrap(),
+        )
+    }
+
+    #[test]
+    fn later_graphics_configs_override_earlier_ones() {
+        let characters = config(
+            "characters.ron",
+            r#"[(file: "hero.png", tile_size: 16.0, rows: 1, columns: 4, animations: [
+                (name: "walk", start: 0, end: 3, fps: 10.0, animation_type: Repeat),
+            ])]"#,
+        );
+        let ui = config(
+            "ui.ron",
+            r#"[(file: "hero.png", tile_size: 32.0, rows: 1, columns: 2)]"#,
+        );
+        let effects = config(
+            "effects.ron",
+            r#"[(file: "fx.png", tile_size: 16.0, rows: 1, columns: 2, animations: [
+                (name: "walk", start: 0, end: 1, fps: 10.0, animation_type: Repeat),
+            ])]"#,
+        );
+
+        let merged = merge_graphics_configs(vec![characters.clone(), ui], false).unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].tile_size, 32.0);
+
+        let merged = merge_graphics_configs(vec![characters, effects], false).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert!(merged[0].animations.is_empty());
+        assert_eq!(merged[1].file, "fx.png");
+        assert_eq!(merged[1].animations[0].name, "walk");
+    }
+
+    #[test]
+    fn strict_merges_reject_collisions() {
+        let characters = config(
+            "characters.ron",
+            r#"[(file: "hero.png", tile_size: 16.0, rows: 1, columns: 4)]"#,
+        );
+        let ui = config(
+            "ui.ron",
+            r#"[(file: "hero.png", tile_size: 32.0, rows: 1, columns: 2)]"#,
+        );
+
+        let collision = merge_graphics_configs(vec![characters, ui], true).unwrap_err();
+        assert_eq!(collision.kind, "sprite sheet");
+        assert_eq!(collision.name, "hero");
+        assert_eq!(collision.first_file, "characters.ron");
+        assert_eq!(collision.second_file, "ui.ron");
+    }
+
+    /// Frames shown, in order, when ticking an animation 0.1s at a time until it finishes
+    fn played(mut animation: Animation) -> Vec<usize> {
+        let mut shown = vec![animation.current_frame()];
+        for _ in 0..100 {
+            if animation.finished() {
+                break;
+            }
+            animation.tick(0.1);
+            shown.push(animation.current_frame());
+        }
+        shown.dedup();
+        shown
+    }
+
+    #[test]
+    fn reversed_animations_play_the_forward_sequence_backwards() {
+        let forward = played(Animation::new(
+            "sheet".to_string(),
+            frames(5),
+            0.1,
+            AnimationType::Once,
+        ));
+        assert_eq!(forward, vec![0, 1, 2, 3, 4]);
+        let backward: Vec<usize> = forward.iter().rev().copied().collect();
+
+        let once_reverse = Animation::new(
+            "sheet".to_string(),
+            frames(5),
+            0.1,
+            AnimationType::OnceReverse,
+        );
+        assert_eq!(played(once_reverse), backward);
+
+        let mut reversed = Animation::new("sheet".to_string(), frames(5), 0.1, AnimationType::Once);
+        reversed.reverse();
+        assert_eq!(played(reversed), backward);
+    }
+
+    #[test]
+    fn empty_graphics_configs_build_empty_resources() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let config = parse_graphics_config(b"[]", ConfigFormat::Ron).unwrap();
+        assert!(config.is_empty());
+
+        let mut app = crate::testing::asset_app();
+        app.world.run_system_once(
+            move |mut commands: Commands,
+                  settings: Res<GFXSettings>,
+                  asset_server: Res<AssetServer>,
+                  mut layouts: ResMut<Assets<TextureAtlasLayout>>| {
+                build_sprite_sheets(
+                    &mut commands,
+                    &settings,
+                    &asset_server,
+                    &mut layouts,
+                    &config,
+                );
+            },
+        );
+
+        assert_eq!(
+            app.world.resource::<SpriteSheetResource>().iter().count(),
+            0
+        );
+        assert_eq!(app.world.resource::<AnimationResource>().names().count(), 0);
+    }
+
+    #[test]
+    fn sprites_with_a_handle_skip_the_sheet_lookup() {
+        // no sheets at all, so any name lookup would leave the sprite pending
+        let mut app = crate::testing::asset_app();
+        app.add_systems(Update, add_sprite_from_sprite_meta);
+        app.world.spawn((Window::default(), PrimaryWindow));
+
+        let handle = SpriteSheetHandle {
+            texture: Handle::default(),
+            layout: Handle::default(),
+            tile_count: 4,
+            normal_map: None,
+        };
+        for i in 0..10_000 {
+            app.world.spawn((
+                SpriteMeta::from_handle("hero", handle.clone(), i % 4),
+                GlobalTransform::default(),
+            ));
+        }
+        app.update();
+
+        let built = app
+            .world
+            .query_filtered::<(), With<SpriteAdded>>()
+            .iter(&app.world)
+            .count();
+        let pending = app
+            .world
+            .query_filtered::<(), With<SpritePending>>()
+            .iter(&app.world)
+            .count();
+        assert_eq!(built, 10_000);
+        assert_eq!(pending, 0);
+    }
+
+    #[test]
+    fn sprites_wait_for_a_window() {
+        let mut app = crate::testing::asset_app();
+        app.add_systems(Update, add_sprite_from_sprite_meta);
+        let entity = app
+            .world
+            .spawn((sprite("hero"), GlobalTransform::default()))
+            .id();
+
+        app.update();
+
+        assert!(!app.world.entity(entity).contains::<SpriteAdded>());
     }
 }
@@ -1,8 +1,24 @@
 use bevy::prelude::*;
 use wasm_bindgen::prelude::*;
-mod gfx;
-mod ron_helpers;
-mod sound;
+pub mod animation_control;
+pub mod camera;
+pub mod embedded;
+pub mod gfx;
+pub mod lighting;
+pub mod loading;
+pub mod palette;
+pub mod pause;
+pub mod presentation;
+pub mod rng;
+pub mod ron_helpers;
+pub mod save;
+pub mod scene;
+pub mod sound;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod text;
+pub mod tilemap;
+pub mod time;
 
 // TODO OTD: Start building example game
 
@@ -11,8 +27,14 @@ pub fn start() {
     App::new()
         .add_plugins((
             DefaultPlugins,
-            gfx::GFXPlugin { snap_camera: false },
-            sound::SoundPlugin {},
+            gfx::GFXPlugin::new().snap_camera(false),
+            sound::SoundPlugin::new(),
+            text::FontPlugin::default(),
+            save::SaveGamePlugin {},
+            loading::LoadingPlugin {},
+            scene::ScenePlugin::default(),
+            time::GameTimerPlugin {},
+            tilemap::TilemapPlugin::default(),
         ))
         .run()
 }
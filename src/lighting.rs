@@ -0,0 +1,276 @@
+use crate::gfx::{
+    atlas_sprite_size, update_animations, GFXSettings, SpriteAdded, SpriteMeta, SpriteSheetResource,
+};
+use bevy::{
+    asset::load_internal_asset,
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    sprite::{Material2d, Material2dPlugin, Mesh2dHandle},
+    window::PrimaryWindow,
+};
+use std::collections::HashMap;
+
+/// Most point lights a lit sprite is shaded by, extra lights are ignored
+pub const MAX_LIGHTS: usize = 8;
+
+const LIT_SPRITE_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(0x2c4f_91d0_7b3e_4a65_8e1d_5f02_b6a9_c317);
+
+/// Added by GFXPlugin, renders sprites whose sheet has a normal map
+pub struct LightingPlugin {}
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            LIT_SPRITE_SHADER,
+            "shaders/lit_sprite.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins(Material2dPlugin::<LitSpriteMaterial>::default())
+            .init_resource::<AmbientLight2d>()
+            .add_systems(
+                Update,
+                (
+                    add_lit_sprites.after(update_animations),
+                    update_lit_sprite_lights.after(add_lit_sprites),
+                ),
+            );
+    }
+}
+
+/// A point light for lit sprites, at the entity's GlobalTransform. `height` lifts it above the
+/// sprites (in world units) so it lights fronts as well as edges
+#[derive(Debug, Clone, Copy, Component)]
+pub struct PointLight2d {
+    pub color: Color,
+    pub intensity: f32,
+    pub radius: f32, // world units, the light fades to nothing at this distance
+    pub height: f32,
+}
+
+impl Default for PointLight2d {
+    fn default() -> Self {
+        PointLight2d {
+            color: Color::WHITE,
+            intensity: 1.0,
+            radius: 5.0,
+            height: 1.0,
+        }
+    }
+}
+
+/// Light every lit sprite gets regardless of point lights. Unlit sprites (sheets without a normal
+/// map) ignore it
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct AmbientLight2d(pub Color);
+
+impl Default for AmbientLight2d {
+    fn default() -> Self {
+        AmbientLight2d(Color::rgb(0.3, 0.3, 0.3))
+    }
+}
+
+/// Added by add_sprite_from_sprite_meta to entities whose sheet has a normal map, they're drawn
+/// with a LitSpriteMaterial instead of a Sprite
+#[derive(Debug, Clone, Copy, Component)]
+pub struct LitSprite {}
+
+#[derive(Debug, Clone, Default, PartialEq, ShaderType)]
+pub struct LitSpriteUniform {
+    pub lights: [Vec4; MAX_LIGHTS], // position (xyz) and radius (w)
+    pub colors: [Vec4; MAX_LIGHTS], // color times intensity
+    pub ambient: Vec4,
+    pub uv_rect: Vec4, // atlas frame as (min u, min v, width, height)
+    pub count: u32,
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct LitSpriteMaterial {
+    #[uniform(0)]
+    pub lighting: LitSpriteUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub normal_map: Handle<Image>,
+}
+
+impl Material2d for LitSpriteMaterial {
+    fn fragment_shader() -> ShaderRef {
+        LIT_SPRITE_SHADER.into()
+    }
+}
+
+///
+/// add_lit_sprites: Bevy system
+///
+/// The lit counterpart of add_sprite_from_sprite_meta: adds or updates the mesh and
+/// LitSpriteMaterial when the SpriteMeta or the window scale factor changes, or the animation moves
+/// to a new frame. If the entity switches to a sheet without a normal map it's handed back to
+/// add_sprite_from_sprite_meta
+///
+/// Like PaletteSwap, each lit entity has its own material and costs a draw call. The mesh isn't
+/// mirrored, so MirrorMode::SpriteFlip has no effect on lit sprites (use TransformScale)
+pub fn add_lit_sprites(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Ref<SpriteMeta>,
+            Option<&Handle<LitSpriteMaterial>>,
+            Has<SpriteAdded>,
+            Has<Visibility>,
+        ),
+        (With<LitSprite>, With<GlobalTransform>),
+    >,
+    sprite_sheet_resource: Res<SpriteSheetResource>,
+    settings: Res<GFXSettings>,
+    ambient: Res<AmbientLight2d>,
+    texture_atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+    mut materials: ResMut<Assets<LitSpriteMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut last_scale_factor: Local<Option<f32>>,
+    mut quads: Local<HashMap<[u32; 2], Handle<Mesh>>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let scale_factor_changed = *last_scale_factor != Some(window.scale_factor());
+    *last_scale_factor = Some(window.scale_factor());
+
+    for (entity, sprite, material, added, has_visibility) in query.iter() {
+        if added && !scale_factor_changed && !sprite.is_changed() {
+            continue;
+        }
+
+        let Some(handle) = sprite
+            .handle
+            .clone()
+            .or_else(|| sprite_sheet_resource.get(&sprite.sheet_name))
+        else {
+            continue; // the sheet may still be loading
+        };
+        let Some(normal_map) = handle.normal_map.clone() else {
+            commands.entity(entity).remove::<(
+                LitSprite,
+                Handle<LitSpriteMaterial>,
+                Mesh2dHandle,
+                SpriteAdded,
+            )>();
+            continue;
+        };
+        let Some(layout) = texture_atlas_layouts.get(&handle.layout) else {
+            continue;
+        };
+        let Some(frame) = layout.textures.get(sprite.index) else {
+            warn!(
+                "Warning: frame {} is out of range for sprite sheet {}",
+                sprite.index, sprite.sheet_name
+            );
+            continue;
+        };
+
+        let uv_rect = Vec4::new(
+            frame.min.x / layout.size.x,
+            frame.min.y / layout.size.y,
+            frame.width() / layout.size.x,
+            frame.height() / layout.size.y,
+        );
+
+        // update the entity's material in place rather than adding a new one every frame, the
+        // lights are filled in by update_lit_sprite_lights
+        match material.and_then(|material| materials.get_mut(material)) {
+            Some(material) => {
+                material.lighting.uv_rect = uv_rect;
+                material.texture = handle.texture.clone();
+                material.normal_map = normal_map;
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(materials.add(LitSpriteMaterial {
+                        lighting: LitSpriteUniform {
+                            ambient: Vec4::from(ambient.0.as_linear_rgba_f32()),
+                            uv_rect,
+                            ..default()
+                        },
+                        texture: handle.texture.clone(),
+                        normal_map,
+                    }));
+            }
+        }
+
+        // one quad per size, shared by every entity of that size
+        let atlas = TextureAtlas {
+            layout: handle.layout.clone(),
+            index: sprite.index,
+        };
+        let size = atlas_sprite_size(
+            &settings,
+            &texture_atlas_layouts,
+            &atlas,
+            window.scale_factor(),
+            sprite.scale,
+        );
+        let quad = quads
+            .entry([size.x.to_bits(), size.y.to_bits()])
+            .or_insert_with(|| meshes.add(Rectangle::from_size(size)))
+            .clone();
+
+        commands
+            .entity(entity)
+            .insert((Mesh2dHandle(quad), SpriteAdded {}));
+
+        if !has_visibility {
+            commands.entity(entity).insert(VisibilityBundle::default());
+        }
+    }
+}
+
+///
+/// update_lit_sprite_lights: Bevy system
+///
+/// Copies the point lights (the first MAX_LIGHTS) and the ambient light into every
+/// LitSpriteMaterial, only touching the materials when something changed
+pub fn update_lit_sprite_lights(
+    ambient: Res<AmbientLight2d>,
+    lights_query: Query<(&PointLight2d, &GlobalTransform)>,
+    sprites_query: Query<Ref<Handle<LitSpriteMaterial>>>,
+    mut materials: ResMut<Assets<LitSpriteMaterial>>,
+    mut last: Local<LitSpriteUniform>,
+) {
+    let mut lighting = LitSpriteUniform {
+        ambient: Vec4::from(ambient.0.as_linear_rgba_f32()),
+        ..default()
+    };
+    for (i, (light, transform)) in lights_query.iter().take(MAX_LIGHTS).enumerate() {
+        let position = transform.translation();
+        lighting.lights[i] = Vec4::new(
+            position.x,
+            position.y,
+            light.height,
+            light.radius.max(f32::EPSILON),
+        );
+        lighting.colors[i] = Vec4::from(light.color.as_linear_rgba_f32()) * light.intensity;
+        lighting.count += 1;
+    }
+
+    let lights_changed = lighting != *last;
+    for material in sprites_query.iter() {
+        if !lights_changed && !material.is_added() {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material.id()) {
+            material.lighting = LitSpriteUniform {
+                uv_rect: material.lighting.uv_rect,
+                ..lighting.clone()
+            };
+        }
+    }
+
+    *last = lighting;
+}
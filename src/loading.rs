@@ -0,0 +1,285 @@
+use crate::{
+    gfx::{AnimationResource, PendingAtlases, PendingGraphicsConfig, SpriteSheetResource},
+    sound::{PendingSoundsConfig, SoundResource},
+};
+use bevy::{asset::LoadState, prelude::*};
+
+pub struct LoadingPlugin {}
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadProgress>()
+            .add_event::<LoadSummary>()
+            .add_systems(Update, update_load_progress)
+            .add_systems(Update, send_load_summary.after(update_load_progress));
+    }
+}
+
+/// How many of the sprite sheet textures and sounds from the configs have finished loading
+///
+/// Updated every frame, e.g. to drive a loading bar
+#[derive(Debug, Clone, Default, PartialEq, Resource)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+    pub configs_loaded: bool, // the graphics and sounds configs are read, so `total` is final
+}
+
+impl LoadProgress {
+    /// Fraction of assets loaded, from 0.0 to 1.0. Stays at 0.0 while the configs are still being
+    /// read (e.g. fetched on WASM)
+    pub fn fraction(&self) -> f32 {
+        if !self.configs_loaded {
+            return 0.0;
+        }
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.loaded as f32 / self.total as f32
+    }
+
+    /// True once the configs are read and every asset in them is either loaded or failed
+    pub fn is_done(&self) -> bool {
+        self.configs_loaded && self.loaded + self.failed >= self.total
+    }
+}
+
+/// Named sprite sheets and sounds that must be loaded before some stage of the game starts (e.g. the
+/// logo and theme music before the title screen), while everything else keeps loading behind it
+///
+/// Everything in the configs starts loading at startup either way, so this only narrows what to
+/// wait for. Insert it as a resource to use the `preloaded` run condition
+#[derive(Debug, Clone, Default, Resource)]
+pub struct PreloadSet {
+    pub sprites: Vec<String>,
+    pub sounds: Vec<String>,
+}
+
+impl PreloadSet {
+    /// True once every listed sheet and sound is loaded. Names that aren't in SpriteSheetResource or
+    /// SoundResource (yet) count as not loaded, as do sheets still waiting in PendingAtlases
+    pub fn is_preloaded(
+        &self,
+        asset_server: &AssetServer,
+        sprite_sheet_resource: &SpriteSheetResource,
+        sound_resource: &SoundResource,
+    ) -> bool {
+        let sprites_loaded = self.sprites.iter().all(|name| {
+            sprite_sheet_resource.get(name).is_some_and(|handle| {
+                matches!(
+                    asset_server.get_load_state(&handle.texture),
+                    Some(LoadState::Loaded)
+                )
+            })
+        });
+
+        let sounds_loaded = self.sounds.iter().all(|name| {
+            sound_resource.get(name).is_some_and(|handle| {
+                matches!(
+                    asset_server.get_load_state(&handle),
+                    Some(LoadState::Loaded)
+                )
+            })
+        });
+
+        sprites_loaded && sounds_loaded
+    }
+}
+
+///
+/// preloaded: run condition
+///
+/// True once everything in the PreloadSet resource is loaded, or if there's no PreloadSet, e.g.
+/// `spawn_title_screen.run_if(preloaded)`
+pub fn preloaded(
+    asset_server: Res<AssetServer>,
+    preload_set: Option<Res<PreloadSet>>,
+    sprite_sheet_resource: Option<Res<SpriteSheetResource>>,
+    sound_resource: Option<Res<SoundResource>>,
+) -> bool {
+    let Some(preload_set) = preload_set else {
+        return true;
+    };
+    let (Some(sprite_sheet_resource), Some(sound_resource)) =
+        (sprite_sheet_resource, sound_resource)
+    else {
+        return false;
+    };
+
+    preload_set.is_preloaded(&asset_server, &sprite_sheet_resource, &sound_resource)
+}
+
+///
+/// update_load_progress: Bevy system
+///
+/// Polls the asset server for every handle in SpriteSheetResource, PendingAtlases and SoundResource.
+/// The configs count as loaded once neither PendingGraphicsConfig nor PendingSoundsConfig is left
+pub fn update_load_progress(
+    asset_server: Res<AssetServer>,
+    sprite_sheet_resource: Option<Res<SpriteSheetResource>>,
+    pending_atlases: Option<Res<PendingAtlases>>,
+    sound_resource: Option<Res<SoundResource>>,
+    pending_configs: (
+        Option<Res<PendingGraphicsConfig>>,
+        Option<Res<PendingSoundsConfig>>,
+    ),
+    mut progress: ResMut<LoadProgress>,
+) {
+    let mut states = Vec::new();
+
+    if let Some(sprite_sheet_resource) = sprite_sheet_resource.as_ref() {
+        sprite_sheet_resource.iter().for_each(|(_, handle)| {
+            states.push(asset_server.get_load_state(&handle.texture));
+        });
+    }
+
+    if let Some(pending_atlases) = pending_atlases.as_ref() {
+        pending_atlases.iter().for_each(|(_, frames)| {
+            frames.iter().for_each(|frame| {
+                states.push(asset_server.get_load_state(frame));
+            });
+        });
+    }
+
+    if let Some(sound_resource) = sound_resource.as_ref() {
+        sound_resource.iter().for_each(|(_, handle)| {
+            states.push(asset_server.get_load_state(handle));
+        });
+    }
+
+    let next = LoadProgress {
+        loaded: states
+            .iter()
+            .filter(|state| matches!(state, Some(LoadState::Loaded)))
+            .count(),
+        failed: states
+            .iter()
+            .filter(|state| matches!(state, Some(LoadState::Failed)))
+            .count(),
+        total: states.len(),
+        configs_loaded: pending_configs.0.is_none() && pending_configs.1.is_none(),
+    };
+
+    if next.ne(&*progress) {
+        *progress = next;
+    }
+}
+
+/// What the configs loaded, sent once at startup after every asset has finished loading (or
+/// failed), and kept as a resource afterwards, e.g. for a content check in CI
+#[derive(Event, Debug, Clone, Default, PartialEq, Resource)]
+pub struct LoadSummary {
+    pub sheets: Vec<String>,
+    pub animations: Vec<String>,
+    pub sounds: Vec<String>,
+    pub failed: Vec<String>, // sheets and sounds whose files failed to load
+    pub skipped_animations: Vec<String>, // animations left out for invalid frames or timing
+}
+
+///
+/// send_load_summary: Bevy system
+///
+/// Sends LoadSummary and inserts it as a resource once LoadProgress is done
+pub fn send_load_summary(
+    mut commands: Commands,
+    mut events: EventWriter<LoadSummary>,
+    asset_server: Res<AssetServer>,
+    progress: Res<LoadProgress>,
+    sprite_sheet_resource: Option<Res<SpriteSheetResource>>,
+    pending_atlases: Option<Res<PendingAtlases>>,
+    animation_resource: Option<Res<AnimationResource>>,
+    sound_resource: Option<Res<SoundResource>>,
+    mut sent: Local<bool>,
+) {
+    if *sent || !progress.is_done() {
+        return;
+    }
+    *sent = true;
+
+    let mut summary = LoadSummary::default();
+    let failed = |state: Option<LoadState>| matches!(state, Some(LoadState::Failed));
+
+    if let Some(sprite_sheet_resource) = sprite_sheet_resource.as_ref() {
+        sprite_sheet_resource.iter().for_each(|(name, handle)| {
+            summary.sheets.push(name.clone());
+            if failed(asset_server.get_load_state(&handle.texture)) {
+                summary.failed.push(name.clone());
+            }
+        });
+    }
+
+    if let Some(pending_atlases) = pending_atlases.as_ref() {
+        pending_atlases.iter().for_each(|(name, frames)| {
+            summary.sheets.push(name.clone());
+            if frames
+                .iter()
+                .any(|frame| failed(asset_server.get_load_state(frame)))
+            {
+                summary.failed.push(name.clone());
+            }
+        });
+    }
+
+    if let Some(animation_resource) = animation_resource.as_ref() {
+        summary.animations = animation_resource.names().map(String::from).collect();
+        summary.skipped_animations = animation_resource.skipped().to_vec();
+    }
+
+    if let Some(sound_resource) = sound_resource.as_ref() {
+        sound_resource.iter().for_each(|(name, handle)| {
+            summary.sounds.push(name.clone());
+            if failed(asset_server.get_load_state(handle)) {
+                summary.failed.push(name.clone());
+            }
+        });
+    }
+
+    // HashMap order isn't stable, sort so summaries can be compared
+    summary.sheets.sort();
+    summary.sheets.dedup();
+    summary.animations.sort();
+    summary.sounds.sort();
+    summary.failed.sort();
+    summary.failed.dedup();
+
+    info!(
+        "Loaded {} sprite sheet(s), {} animation(s) and {} sound(s), {} failed, {} animation(s) skipped",
+        summary.sheets.len(),
+        summary.animations.len(),
+        summary.sounds.len(),
+        summary.failed.len(),
+        summary.skipped_animations.len()
+    );
+
+    events.send(summary.clone());
+    commands.insert_resource(summary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_waits_for_the_configs() {
+        let reading = LoadProgress::default();
+        assert!(!reading.is_done());
+        assert_eq!(reading.fraction(), 0.0);
+
+        let read = LoadProgress {
+            configs_loaded: true,
+            ..default()
+        };
+        assert!(read.is_done());
+        assert_eq!(read.fraction(), 1.0);
+
+        let loading = LoadProgress {
+            loaded: 1,
+            total: 4,
+            configs_loaded: true,
+            ..default()
+        };
+        assert!(!loading.is_done());
+        assert_eq!(loading.fraction(), 0.25);
+    }
+}
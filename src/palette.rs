@@ -0,0 +1,263 @@
+use crate::gfx::{
+    atlas_sprite_size, update_animations, GFXSettings, SpriteAdded, SpriteMeta, SpriteSheetResource,
+};
+use bevy::{
+    asset::load_internal_asset,
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    sprite::{Material2d, Material2dPlugin, Mesh2dHandle},
+    window::PrimaryWindow,
+};
+use std::collections::HashMap;
+
+/// Most colors a single PaletteSwap can replace, extra pairs are ignored
+pub const MAX_PALETTE_COLORS: usize = 16;
+
+const PALETTE_SWAP_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(0x5a1e_77e5_3a9d_4c1b_9f0e_64c2_d43b_a8e1);
+
+/// Added by GFXPlugin, renders entities with a PaletteSwap
+pub struct PaletteSwapPlugin {}
+
+impl Plugin for PaletteSwapPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            PALETTE_SWAP_SHADER,
+            "shaders/palette_swap.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins(Material2dPlugin::<PaletteSwapMaterial>::default())
+            .add_systems(
+                Update,
+                (
+                    remove_palette_swap_sprites.before(add_palette_swap_sprites),
+                    add_palette_swap_sprites.after(update_animations),
+                ),
+            );
+    }
+}
+
+///
+/// PaletteSwap
+///
+/// Recolors an entity's sprite at render time: every pixel matching `from[i]` is drawn as `to[i]`,
+/// e.g. for team colors on a shared sprite sheet. Up to MAX_PALETTE_COLORS pairs
+///
+/// The entity is drawn as a mesh with its own PaletteSwapMaterial instead of a Sprite (a Sprite it
+/// already had is removed, and rebuilt when the PaletteSwap is removed). Sprites
+/// are batched together, materials aren't, so each recolored entity costs a draw call. Fine for a
+/// handful of characters, use a flat `Sprite.color` tint (or a recolored sheet) for crowds
+#[derive(Debug, Clone, Default, Component)]
+pub struct PaletteSwap {
+    pub from: Vec<Color>,
+    pub to: Vec<Color>,
+}
+
+impl PaletteSwap {
+    pub fn new(from: Vec<Color>, to: Vec<Color>) -> Self {
+        PaletteSwap { from, to }
+    }
+}
+
+#[derive(Debug, Clone, Default, ShaderType)]
+pub struct PaletteSwapUniform {
+    pub from: [Vec4; MAX_PALETTE_COLORS],
+    pub to: [Vec4; MAX_PALETTE_COLORS],
+    pub uv_rect: Vec4, // atlas frame as (min u, min v, width, height)
+    pub count: u32,
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct PaletteSwapMaterial {
+    #[uniform(0)]
+    pub palette: PaletteSwapUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+}
+
+impl Material2d for PaletteSwapMaterial {
+    fn fragment_shader() -> ShaderRef {
+        PALETTE_SWAP_SHADER.into()
+    }
+}
+
+/// Uniform for a PaletteSwap showing the given atlas frame. Colors are compared in linear space,
+/// the same space the texture is sampled in
+fn palette_uniform(palette_swap: &PaletteSwap, uv_rect: Vec4) -> PaletteSwapUniform {
+    let mut uniform = PaletteSwapUniform {
+        uv_rect,
+        ..default()
+    };
+
+    let pairs = palette_swap.from.iter().zip(palette_swap.to.iter());
+    for (i, (from, to)) in pairs.take(MAX_PALETTE_COLORS).enumerate() {
+        uniform.from[i] = Vec4::from(from.as_linear_rgba_f32());
+        uniform.to[i] = Vec4::from(to.as_linear_rgba_f32());
+        uniform.count += 1;
+    }
+
+    uniform
+}
+
+///
+/// add_palette_swap_sprites: Bevy system
+///
+/// The PaletteSwap counterpart of add_sprite_from_sprite_meta (which skips these entities): adds or
+/// updates the mesh and PaletteSwapMaterial when the SpriteMeta, the PaletteSwap or the window scale
+/// factor changes, or the animation moves to a new frame
+pub fn add_palette_swap_sprites(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Ref<SpriteMeta>,
+            Ref<PaletteSwap>,
+            Option<&Handle<PaletteSwapMaterial>>,
+            Has<SpriteAdded>,
+            Has<Visibility>,
+        ),
+        With<GlobalTransform>,
+    >,
+    sprite_sheet_resource: Res<SpriteSheetResource>,
+    settings: Res<GFXSettings>,
+    texture_atlas_layouts: Res<Assets<TextureAtlasLayout>>,
+    mut materials: ResMut<Assets<PaletteSwapMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut last_scale_factor: Local<Option<f32>>,
+    mut quads: Local<HashMap<[u32; 2], Handle<Mesh>>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let scale_factor_changed = *last_scale_factor != Some(window.scale_factor());
+    *last_scale_factor = Some(window.scale_factor());
+
+    for (entity, sprite, palette_swap, material, added, has_visibility) in query.iter() {
+        if added && !scale_factor_changed && !sprite.is_changed() && !palette_swap.is_changed() {
+            continue;
+        }
+
+        let Some(handle) = sprite
+            .handle
+            .clone()
+            .or_else(|| sprite_sheet_resource.get(&sprite.sheet_name))
+        else {
+            continue; // the sheet may still be loading
+        };
+        let Some(layout) = texture_atlas_layouts.get(&handle.layout) else {
+            continue;
+        };
+        let Some(frame) = layout.textures.get(sprite.index) else {
+            warn!(
+                "Warning: frame {} is out of range for sprite sheet {}",
+                sprite.index, sprite.sheet_name
+            );
+            continue;
+        };
+
+        let uv_rect = Vec4::new(
+            frame.min.x / layout.size.x,
+            frame.min.y / layout.size.y,
+            frame.width() / layout.size.x,
+            frame.height() / layout.size.y,
+        );
+        let uniform = palette_uniform(&palette_swap, uv_rect);
+
+        // update the entity's material in place rather than adding a new one every frame
+        match material.and_then(|material| materials.get_mut(material)) {
+            Some(material) => {
+                material.palette = uniform;
+                material.texture = handle.texture.clone();
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(materials.add(PaletteSwapMaterial {
+                        palette: uniform,
+                        texture: handle.texture.clone(),
+                    }));
+            }
+        }
+
+        // one quad per size, shared by every entity of that size
+        let atlas = TextureAtlas {
+            layout: handle.layout.clone(),
+            index: sprite.index,
+        };
+        let size = atlas_sprite_size(
+            &settings,
+            &texture_atlas_layouts,
+            &atlas,
+            window.scale_factor(),
+            sprite.scale,
+        );
+        let quad = quads
+            .entry([size.x.to_bits(), size.y.to_bits()])
+            .or_insert_with(|| meshes.add(Rectangle::from_size(size)))
+            .clone();
+
+        commands
+            .entity(entity)
+            .insert((Mesh2dHandle(quad), SpriteAdded {}));
+
+        // a PaletteSwap added to a built sprite replaces it, or both would be drawn
+        if palette_swap.is_added() {
+            commands
+                .entity(entity)
+                .remove::<(Sprite, Handle<Image>, TextureAtlas)>();
+        }
+
+        if !has_visibility {
+            commands.entity(entity).insert(VisibilityBundle::default());
+        }
+    }
+}
+
+///
+/// remove_palette_swap_sprites: Bevy system
+///
+/// Removes the mesh and PaletteSwapMaterial of entities that lost their PaletteSwap, along with
+/// SpriteAdded, so add_sprite_from_sprite_meta draws them as a plain Sprite again
+pub fn remove_palette_swap_sprites(
+    mut commands: Commands,
+    mut removed: RemovedComponents<PaletteSwap>,
+) {
+    for entity in removed.read() {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.remove::<(Mesh2dHandle, Handle<PaletteSwapMaterial>, SpriteAdded)>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_a_palette_swap_brings_back_the_sprite() {
+        let mut app = App::new();
+        app.add_systems(Update, remove_palette_swap_sprites);
+        let entity = app
+            .world
+            .spawn((
+                PaletteSwap::new(vec![Color::RED], vec![Color::BLUE]),
+                Mesh2dHandle(Handle::default()),
+                Handle::<PaletteSwapMaterial>::default(),
+                SpriteAdded {},
+            ))
+            .id();
+        app.update();
+
+        app.world.entity_mut(entity).remove::<PaletteSwap>();
+        app.update();
+
+        let entity = app.world.entity(entity);
+        assert!(!entity.contains::<Mesh2dHandle>());
+        assert!(!entity.contains::<Handle<PaletteSwapMaterial>>());
+        assert!(!entity.contains::<SpriteAdded>());
+    }
+}
@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+
+/// Game-wide pause: while true, animations stop ticking and the current song is paused (SFX too
+/// if `SoundPlugin.pause_sfx` is set). Both GFXPlugin and SoundPlugin add this resource
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct GamePaused(pub bool);
+
+impl GamePaused {
+    pub fn is_paused(&self) -> bool {
+        self.0
+    }
+
+    pub fn toggle(&mut self) {
+        self.0 = !self.0;
+    }
+}
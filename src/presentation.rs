@@ -0,0 +1,246 @@
+use crate::camera::MainCamera;
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+/// Fades a full-window overlay to `to` over `duration` seconds (real time, so it runs while paused),
+/// drawn above everything including UI. Fade out with `Color::BLACK`, then back in with
+/// `Color::rgba(0.0, 0.0, 0.0, 0.0)`. A new fade starts from wherever the current one is
+#[derive(Event, Debug, Clone)]
+pub struct ScreenFade {
+    pub to: Color,
+    pub duration: f32,
+}
+
+impl ScreenFade {
+    pub fn new(to: Color, duration: f32) -> Self {
+        ScreenFade { to, duration }
+    }
+}
+
+/// Sent when a ScreenFade reaches its target color
+#[derive(Event, Debug, Clone)]
+pub struct ScreenFadeComplete {
+    pub color: Color,
+}
+
+/// The full-window node used by ScreenFade
+#[derive(Debug, Component)]
+pub struct ScreenFadeOverlay {
+    from: Color,
+    to: Color,
+    timer: Timer,
+    done: bool,
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let [r0, g0, b0, a0] = from.as_rgba_f32();
+    let [r1, g1, b1, a1] = to.as_rgba_f32();
+    Color::rgba(
+        r0 + (r1 - r0) * t,
+        g0 + (g1 - g0) * t,
+        b0 + (b1 - b0) * t,
+        a0 + (a1 - a0) * t,
+    )
+}
+
+pub fn start_screen_fade(
+    mut commands: Commands,
+    mut events: EventReader<ScreenFade>,
+    mut overlay_query: Query<(&mut ScreenFadeOverlay, &BackgroundColor)>,
+) {
+    // only the latest fade matters
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let timer = Timer::from_seconds(event.duration.max(0.0), TimerMode::Once);
+
+    if let Ok((mut overlay, background)) = overlay_query.get_single_mut() {
+        *overlay = ScreenFadeOverlay {
+            from: background.0,
+            to: event.to,
+            timer,
+            done: false,
+        };
+        return;
+    }
+
+    // fading in from nothing starts from the target color with no alpha
+    let from = event.to.with_a(0.0);
+    commands.spawn((
+        ScreenFadeOverlay {
+            from,
+            to: event.to,
+            timer,
+            done: false,
+        },
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: BackgroundColor(from),
+            z_index: ZIndex::Global(i32::MAX),
+            ..default()
+        },
+    ));
+}
+
+///
+/// update_screen_fade: Bevy system
+///
+/// Moves the ScreenFadeOverlay toward its target color and sends ScreenFadeComplete when it arrives
+pub fn update_screen_fade(
+    time: Res<Time<Real>>,
+    mut complete: EventWriter<ScreenFadeComplete>,
+    mut overlay_query: Query<(&mut ScreenFadeOverlay, &mut BackgroundColor)>,
+) {
+    for (mut overlay, mut background) in overlay_query.iter_mut() {
+        if overlay.done {
+            continue;
+        }
+
+        overlay.timer.tick(time.delta());
+        let t = if overlay.timer.duration().is_zero() {
+            1.0
+        } else {
+            overlay.timer.fraction()
+        };
+        background.0 = lerp_color(overlay.from, overlay.to, t);
+
+        if overlay.timer.finished() {
+            overlay.done = true;
+            complete.send(ScreenFadeComplete { color: overlay.to });
+        }
+    }
+}
+
+/// Keeps a sprite upright relative to the MainCamera even when a parent entity rotates
+///
+/// Only the entity's own GlobalTransform is corrected, its children keep the parent's rotation.
+/// snap_camera_to_focus only moves the camera, so billboards simply stay unrotated when following
+#[derive(Debug, Component)]
+pub struct Billboard {}
+
+///
+/// update_billboards: Bevy system
+///
+/// Replaces the rotation of every Billboard's GlobalTransform with the camera's, after transform
+/// propagation so parent rotation is already applied
+pub fn update_billboards(
+    camera_query: Query<&GlobalTransform, (With<MainCamera>, Without<Billboard>)>,
+    mut billboard_query: Query<&mut GlobalTransform, With<Billboard>>,
+) {
+    let Some(camera) = camera_query.iter().next() else {
+        return;
+    };
+    let (_, camera_rotation, _) = camera.to_scale_rotation_translation();
+
+    for mut global_transform in billboard_query.iter_mut() {
+        let (scale, _, translation) = global_transform.to_scale_rotation_translation();
+        *global_transform = GlobalTransform::from(Transform {
+            translation,
+            rotation: camera_rotation,
+            scale,
+        });
+    }
+}
+
+/// Below this speed (world units per second) FaceVelocity keeps the current rotation
+pub const FACE_VELOCITY_MIN_SPEED: f32 = 0.001;
+
+/// Rotates the entity to point along `velocity`, e.g. for projectiles and vehicles. Sprites are
+/// assumed to be drawn pointing right, set `offset` (radians) otherwise, e.g. `-FRAC_PI_2` for
+/// sprites drawn pointing up. Update `velocity` from your movement code
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct FaceVelocity {
+    pub velocity: Vec2,
+    pub offset: f32,
+}
+
+///
+/// face_velocity: Bevy system
+///
+/// Sets the rotation of every FaceVelocity entity from its velocity, skipping near-zero velocities
+/// so a stopped entity doesn't snap to an arbitrary angle
+pub fn face_velocity(mut query: Query<(&FaceVelocity, &mut Transform)>) {
+    query.iter_mut().for_each(|(face, mut transform)| {
+        if face.velocity.length() < FACE_VELOCITY_MIN_SPEED {
+            return;
+        }
+
+        let rotation = Quat::from_rotation_z(face.velocity.y.atan2(face.velocity.x) + face.offset);
+        if transform.rotation != rotation {
+            transform.rotation = rotation;
+        }
+    });
+}
+
+/// Marker for the FPS overlay text spawned with GFXPlugin.show_fps. Set its Visibility to hide it
+#[derive(Debug, Clone, Copy, Component)]
+pub struct FpsOverlay {}
+
+///
+/// spawn_fps_overlay: Bevy system
+///
+/// Spawns the FPS overlay as UI text, so it stays in the corner whatever the cameras do
+pub fn spawn_fps_overlay(mut commands: Commands) {
+    commands.spawn((
+        FpsOverlay {},
+        TextBundle {
+            text: Text::from_section(
+                "FPS: -",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(4.0),
+                left: Val::Px(4.0),
+                ..default()
+            },
+            // under the screen fade
+            z_index: ZIndex::Global(i32::MAX - 1),
+            ..default()
+        },
+    ));
+}
+
+///
+/// update_fps_overlay: Bevy system
+///
+/// Rewrites the FPS overlay once a second with the smoothed FPS and frame time
+pub fn update_fps_overlay(
+    time: Res<Time<Real>>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut since_update: Local<f32>,
+    mut query: Query<&mut Text, With<FpsOverlay>>,
+) {
+    *since_update += time.delta_seconds();
+    if *since_update < 1.0 {
+        return;
+    }
+    *since_update = 0.0;
+
+    let smoothed = |path| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.smoothed())
+    };
+    let (Some(fps), Some(frame_time)) = (
+        smoothed(&FrameTimeDiagnosticsPlugin::FPS),
+        smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+    ) else {
+        return;
+    };
+
+    query.iter_mut().for_each(|mut text| {
+        text.sections[0].value = format!("FPS: {:.0} ({:.1} ms)", fps, frame_time);
+    });
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+///
+/// GameRng
+///
+/// Small shared random number generator (xorshift64*), so features that need a bit of randomness
+/// (sound variations, effects) draw from one source instead of each seeding their own. Not suitable
+/// for anything security related
+///
+/// The default seed comes from the standard library's hash seed, which is fixed on some WASM
+/// targets. Insert `GameRng::from_seed(..)` for reproducible runs, e.g. replays or tests
+#[derive(Debug, Clone, Resource)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::from_seed(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        // xorshift gets stuck on a zero state
+        GameRng {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Random index below `len`, 0 if `len` is 0
+    pub fn index(&mut self, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        (self.next_u64() % len as u64) as usize
+    }
+
+    /// Random value from 0.0 (inclusive) to 1.0 (exclusive)
+    pub fn fraction(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Random value from min (inclusive) to max (exclusive)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.fraction()
+    }
+}
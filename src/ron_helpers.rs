@@ -1,8 +1,13 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
 use regex::Regex;
 use ron::{
-    de::from_reader,
+    extensions::Extensions,
     ser::{to_string_pretty, PrettyConfig},
-    to_string,
+    to_string, Options,
 };
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
@@ -28,12 +33,235 @@ pub fn save<P: AsRef<Path>, T: Debug + Serialize>(
     Ok(file.write_all(serialized.as_bytes())?)
 }
 
+pub fn serialize<T: Debug + Serialize>(
+    t: &T,
+    pretty: Option<PrettyConfig>,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(config) = pretty {
+        Ok(to_string_pretty(t, config)?)
+    } else {
+        Ok(to_string(t)?)
+    }
+}
+
+/// RON options used for every config: optional fields can be written as plain values
+/// (`fps: 12.0`), `Some(12.0)` works too
+fn ron_options() -> Options {
+    Options::default().with_default_extension(Extensions::IMPLICIT_SOME)
+}
+
+pub fn parse_bytes<T: Debug + DeserializeOwned>(bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+    Ok(ron_options().from_bytes(bytes)?)
+}
+
 pub fn parse<T: Debug + DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
     let f = fs::read(&path)?;
-    let parsed: T = from_reader(&f[..])?;
+    let parsed: T = ron_options().from_reader(&f[..])?;
     Ok(parsed)
 }
 
+///
+/// ConfigFormat
+///
+/// Formats a config file can be written in, picked from the file extension. RON is always
+/// available, JSON and TOML need the `json` and `toml` cargo features
+///
+/// TOML has no top-level arrays, so list configs are written as `[[entries]]` tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Ron,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Every format enabled in this build, in lookup order
+    pub const ENABLED: &'static [ConfigFormat] = &[
+        ConfigFormat::Ron,
+        #[cfg(feature = "json")]
+        ConfigFormat::Json,
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Ron => "ron",
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => "json",
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => "toml",
+        }
+    }
+
+    /// Format for a path, based on its extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ConfigFormat> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        ConfigFormat::ENABLED
+            .iter()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+            .copied()
+    }
+}
+
+/// TOML documents must be tables, so lists are wrapped in an `entries` key
+#[cfg(feature = "toml")]
+#[derive(serde::Deserialize)]
+struct TomlList<T> {
+    entries: Vec<T>,
+}
+
+pub fn parse_bytes_as<T: Debug + DeserializeOwned>(
+    bytes: &[u8],
+    format: ConfigFormat,
+) -> Result<T, Box<dyn Error>> {
+    match format {
+        ConfigFormat::Ron => parse_bytes(bytes),
+        #[cfg(feature = "json")]
+        ConfigFormat::Json => Ok(serde_json::from_slice(bytes)?),
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml => Ok(toml::from_str(std::str::from_utf8(bytes)?)?),
+    }
+}
+
+/// Parses a list config (`Vec<T>`) in any format, see ConfigFormat for TOML
+pub fn parse_list_as<T: Debug + DeserializeOwned>(
+    bytes: &[u8],
+    format: ConfigFormat,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    match format {
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml => {
+            let list: TomlList<T> = toml::from_str(std::str::from_utf8(bytes)?)?;
+            Ok(list.entries)
+        }
+        _ => parse_bytes_as(bytes, format),
+    }
+}
+
+/// Finds `{dir}/{stem}.{ext}` for the first enabled format that exists
+pub fn find_config(dir: &str, stem: &str) -> Result<(String, ConfigFormat), Box<dyn Error>> {
+    ConfigFormat::ENABLED
+        .iter()
+        .map(|format| (format!("{}/{}.{}", dir, stem, format.extension()), *format))
+        .find(|(path, _)| Path::new(path).exists())
+        .ok_or_else(|| format!("no {} file found in {}", stem, dir).into())
+}
+
+/// Reads and parses a config file from disk, naming the file in any error
+pub fn read_config_file<T>(
+    path: &str,
+    format: ConfigFormat,
+    parse: impl FnOnce(&[u8], ConfigFormat) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    fs::read(path)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|bytes| parse(&bytes, format))
+        .map_err(|e| format!("{}: {}", path, e).into())
+}
+
+///
+/// RonAsset
+///
+/// The raw bytes of a config file, loaded through the AssetServer. WASM builds have no filesystem
+/// for `fs::read`, so the plugins load their config.ron this way there and parse it once loaded
+#[derive(Asset, TypePath, Debug)]
+pub struct RonAsset {
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct RonAssetLoader {}
+
+impl AssetLoader for RonAssetLoader {
+    type Asset = RonAsset;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<RonAsset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(RonAsset { bytes })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Registers RonAsset and its loader, safe to call from several plugins
+pub fn register_ron_asset(app: &mut App) {
+    if !app.world.contains_resource::<Assets<RonAsset>>() {
+        app.init_asset::<RonAsset>()
+            .init_asset_loader::<RonAssetLoader>();
+    }
+}
+
+/// Loading state of a config requested with RonAsset: the bytes once loaded, or an error if the
+/// file couldn't be loaded. None while still loading
+pub fn loaded_config(
+    asset_server: &AssetServer,
+    ron_assets: &Assets<RonAsset>,
+    handle: &Handle<RonAsset>,
+) -> Option<Result<Vec<u8>, Box<dyn Error>>> {
+    if let Some(asset) = ron_assets.get(handle) {
+        return Some(Ok(asset.bytes.clone()));
+    }
+    match asset_server.get_load_state(handle) {
+        Some(bevy::asset::LoadState::Failed) => {
+            Some(Err(format!("could not load {:?}", handle.path()).into()))
+        }
+        _ => None,
+    }
+}
+
 pub fn trim_extension(s: &str) -> String {
     Regex::new(r"\.[^.]+$").unwrap().replace(s, "").into_owned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trim_extension_removes_only_the_last_extension() {
+        assert_eq!(trim_extension("hero.png"), "hero");
+        assert_eq!(trim_extension("music.theme.ogg"), "music.theme");
+        assert_eq!(trim_extension("sub/dir/tiles.png"), "sub/dir/tiles");
+        assert_eq!(trim_extension("no_extension"), "no_extension");
+    }
+
+    #[test]
+    fn config_file_errors_name_the_file() {
+        let err = read_config_file(
+            "./no/such/config.ron",
+            ConfigFormat::Ron,
+            |bytes, format| parse_list_as::<String>(bytes, format),
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("./no/such/config.ron: "));
+    }
+
+    #[test]
+    fn parses_ron_lists() {
+        let list: Vec<String> = parse_list_as(br#"["a.ogg", "b.ogg"]"#, ConfigFormat::Ron).unwrap();
+        assert_eq!(list, vec!["a.ogg".to_string(), "b.ogg".to_string()]);
+    }
+
+    #[test]
+    fn optional_values_can_be_written_plain_or_wrapped() {
+        let plain: Vec<Option<f32>> = parse_bytes(b"[12.0, None]").unwrap();
+        let wrapped: Vec<Option<f32>> = parse_bytes(b"[Some(12.0), None]").unwrap();
+        assert_eq!(plain, vec![Some(12.0), None]);
+        assert_eq!(plain, wrapped);
+    }
+}
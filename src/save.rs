@@ -0,0 +1,75 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ron_helpers::{parse, save};
+#[cfg(target_arch = "wasm32")]
+use crate::ron_helpers::{parse_bytes, serialize};
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{error::Error, fmt::Debug};
+
+pub struct SaveGamePlugin {}
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, create_saves_dir);
+    }
+}
+
+/// Save slots are written here on native builds. On WASM they go to the browser's LocalStorage
+pub const SAVES_DIR: &str = "./saves";
+
+pub fn slot_path(slot: u32) -> String {
+    format!("{}/slot_{}.ron", SAVES_DIR, slot)
+}
+
+///
+/// create_saves_dir: Bevy system
+///
+/// Creates the saves folder if it is missing (native only)
+pub fn create_saves_dir() {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(e) = std::fs::create_dir_all(SAVES_DIR) {
+        warn!("Could not create {}: {}", SAVES_DIR, e);
+    }
+}
+
+/// Writes `state` to the given save slot as pretty RON
+pub fn save_state<T: Debug + Serialize>(state: &T, slot: u32) -> Result<(), Box<dyn Error>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::fs::create_dir_all(SAVES_DIR)?;
+        save(state, slot_path(slot), Some(PrettyConfig::default()))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let serialized = serialize(state, Some(PrettyConfig::default()))?;
+        local_storage()?
+            .set_item(&slot_path(slot), &serialized)
+            .map_err(|_| "could not write to LocalStorage".into())
+    }
+}
+
+/// Reads the given save slot back into a `T`
+pub fn load_state<T: Debug + DeserializeOwned>(slot: u32) -> Result<T, Box<dyn Error>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        parse(&slot_path(slot))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let serialized = local_storage()?
+            .get_item(&slot_path(slot))
+            .map_err(|_| "could not read from LocalStorage")?
+            .ok_or_else(|| format!("save slot {} is empty", slot))?;
+        parse_bytes(serialized.as_bytes())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, Box<dyn Error>> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| "LocalStorage is not available".into())
+}
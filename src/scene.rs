@@ -0,0 +1,243 @@
+use crate::{
+    camera::{HasCameraFocus, MainCamera},
+    gfx::{
+        load_sprite_sheets, AnimatedSpriteBundle, Animation, AnimationResource, GFXSettings,
+        PendingGraphicsConfig, SpriteLayer, SpriteMeta, SpriteSheetResource,
+    },
+    ron_helpers::{loaded_config, parse_bytes, register_ron_asset, RonAsset},
+    sound::{load_sounds, SoundSettings, StopMusic},
+};
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use serde::Deserialize;
+use std::path::Path;
+
+pub struct ScenePlugin {
+    pub scene_file: String, // entities to spawn at startup, relative to the Bevy asset root (optional)
+}
+
+impl Default for ScenePlugin {
+    fn default() -> Self {
+        ScenePlugin {
+            scene_file: "scene.ron".to_string(),
+        }
+    }
+}
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        register_ron_asset(app);
+
+        app.insert_resource(SceneSettings {
+            scene_file: self.scene_file.clone(),
+        })
+        .add_event::<ResetScene>()
+        .add_event::<SceneReset>()
+        .add_systems(Startup, request_scene)
+        .add_systems(
+            Update,
+            load_scene.run_if(
+                resource_exists::<PendingScene>
+                    .and_then(not(resource_exists::<PendingGraphicsConfig>)),
+            ),
+        )
+        .add_systems(Last, reset_scene.run_if(on_event::<ResetScene>()));
+    }
+}
+
+/// Settings copied from ScenePlugin so systems can read them
+#[derive(Debug, Clone, Resource)]
+pub struct SceneSettings {
+    pub scene_file: String,
+}
+
+/// One entity in scene.ron
+#[derive(Debug, Clone, Deserialize)]
+pub struct SceneEntityConfig {
+    pub sheet: String,
+    #[serde(default)]
+    pub index: usize, // frame shown when there's no animation
+    #[serde(default)]
+    pub animation: Option<String>, // played from the start, its own sheet is used
+    #[serde(default)]
+    pub position: (f32, f32), // world units
+    #[serde(default)]
+    pub z: f32,
+    #[serde(default)]
+    pub camera_focus: bool, // adds HasCameraFocus, only one entity can have it
+}
+
+/// The scene file requested at startup
+#[derive(Debug, Resource)]
+pub struct PendingScene(pub Handle<RonAsset>);
+
+///
+/// request_scene: Bevy system
+///
+/// Starts loading the scene file, if there is one
+pub fn request_scene(
+    mut commands: Commands,
+    settings: Res<SceneSettings>,
+    asset_server: Res<AssetServer>,
+) {
+    // the scene is optional, don't have the asset server log a missing file as an error
+    if !cfg!(target_arch = "wasm32")
+        && !Path::new(&format!("./assets/{}", settings.scene_file)).exists()
+    {
+        return;
+    }
+
+    commands.insert_resource(PendingScene(asset_server.load(settings.scene_file.clone())));
+}
+
+///
+/// load_scene: Bevy system
+///
+/// Spawns the entities listed in the scene file once it and the graphics config have loaded.
+/// Malformed entries, unknown sheets and unknown animations are skipped with a warning
+pub fn load_scene(
+    mut commands: Commands,
+    settings: Res<SceneSettings>,
+    asset_server: Res<AssetServer>,
+    ron_assets: Res<Assets<RonAsset>>,
+    pending: Res<PendingScene>,
+    sprite_sheet_resource: Res<SpriteSheetResource>,
+    animation_resource: Res<AnimationResource>,
+) {
+    let Some(bytes) = loaded_config(&asset_server, &ron_assets, &pending.0) else {
+        return;
+    };
+    commands.remove_resource::<PendingScene>();
+
+    // parsed entry by entry so one bad entry doesn't lose the whole scene
+    let entries = match bytes.and_then(|bytes| parse_bytes::<Vec<ron::Value>>(&bytes)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Warning: could not parse {}: {}", settings.scene_file, e);
+            return;
+        }
+    };
+
+    let mut has_focus = false;
+    let mut spawned = 0;
+    for (position, entry) in entries.into_iter().enumerate() {
+        let entry: SceneEntityConfig = match entry.into_rust() {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Warning: skipping scene entry {}: {}", position, e);
+                continue;
+            }
+        };
+
+        let transform = Transform::from_xyz(entry.position.0, entry.position.1, entry.z);
+        let mut entity = match &entry.animation {
+            Some(name) => match animation_resource.bundle(name, transform) {
+                Some(bundle) => commands.spawn(bundle),
+                None => {
+                    warn!(
+                        "Warning: skipping scene entry {}: no animation named {}",
+                        position, name
+                    );
+                    continue;
+                }
+            },
+            None if sprite_sheet_resource.get(&entry.sheet).is_some() => commands.spawn((
+                SpriteMeta {
+                    index: entry.index,
+                    sheet_name: entry.sheet.clone(),
+                    ..default()
+                },
+                SpatialBundle::from_transform(transform),
+            )),
+            None => {
+                warn!(
+                    "Warning: skipping scene entry {}: no sprite sheet named {}",
+                    position, entry.sheet
+                );
+                continue;
+            }
+        };
+
+        if entry.camera_focus {
+            if has_focus {
+                warn!(
+                    "Warning: scene entry {} is also marked camera_focus, only the first one is used",
+                    position
+                );
+            } else {
+                entity.insert(HasCameraFocus {});
+                has_focus = true;
+            }
+        }
+        spawned += 1;
+    }
+
+    info!("{} entities spawned from {}", spawned, settings.scene_file);
+}
+
+/// Tears down the current level, e.g. before loading the next one. Listen for SceneReset to know
+/// when it's done
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ResetScene {
+    pub despawn_sprites: bool, // despawns every entity with a SpriteMeta or Animation (cameras are kept)
+    pub reload_config: bool, // re-reads the graphics and sound configs, rebuilding SpriteSheetResource, AnimationResource and SoundResource
+}
+
+impl Default for ResetScene {
+    fn default() -> Self {
+        ResetScene {
+            despawn_sprites: true,
+            reload_config: false,
+        }
+    }
+}
+
+/// Sent once a ResetScene has been carried out, the next level can be spawned from here
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SceneReset {}
+
+///
+/// reset_scene: Bevy system
+///
+/// Handles ResetScene: despawns the sprites, stops the music and optionally reloads the configs.
+/// Runs in Last so nothing spawned this frame is missed, several requests in one frame are merged
+pub fn reset_scene(
+    mut commands: Commands,
+    mut events: EventReader<ResetScene>,
+    stop_music: Option<ResMut<Events<StopMusic>>>,
+    query: Query<
+        Entity,
+        (
+            Or<(With<SpriteMeta>, With<Animation>)>,
+            Without<MainCamera>,
+            Without<SpriteLayer>, // despawned along with their parent
+        ),
+    >,
+) {
+    let (despawn_sprites, reload_config) = events.read().fold((false, false), |acc, event| {
+        (acc.0 || event.despawn_sprites, acc.1 || event.reload_config)
+    });
+
+    if despawn_sprites {
+        query.iter().for_each(|entity| {
+            commands.entity(entity).despawn_recursive();
+        });
+    }
+
+    if let Some(mut stop_music) = stop_music {
+        stop_music.send(StopMusic::new());
+    }
+
+    commands.add(move |world: &mut World| {
+        if reload_config {
+            if world.contains_resource::<GFXSettings>() {
+                world.run_system_once(load_sprite_sheets);
+            }
+            if world.contains_resource::<SoundSettings>() {
+                world.run_system_once(load_sounds);
+            }
+            info!("Configs reloaded");
+        }
+
+        world.send_event(SceneReset {});
+    });
+}
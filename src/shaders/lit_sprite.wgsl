@@ -0,0 +1,39 @@
+// Lit sprites: the albedo is shaded by point lights using the matching normal map texel
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+const MAX_LIGHTS: u32 = 8u;
+
+struct LitSprite {
+    // light position (xyz, z is the height above the sprites) and radius (w)
+    lights: array<vec4<f32>, MAX_LIGHTS>,
+    // light color premultiplied by intensity
+    colors: array<vec4<f32>, MAX_LIGHTS>,
+    ambient: vec4<f32>,
+    // atlas frame as (min u, min v, width, height)
+    uv_rect: vec4<f32>,
+    count: u32,
+};
+
+@group(2) @binding(0) var<uniform> lit: LitSprite;
+@group(2) @binding(1) var base_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_sampler: sampler;
+@group(2) @binding(3) var normal_texture: texture_2d<f32>;
+@group(2) @binding(4) var normal_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = lit.uv_rect.xy + mesh.uv * lit.uv_rect.zw;
+    let albedo = textureSample(base_texture, base_sampler, uv);
+    // normal maps store -1..1 as 0..1, with +y pointing up the image
+    let normal = normalize(textureSample(normal_texture, normal_sampler, uv).rgb * 2.0 - 1.0);
+
+    var light = lit.ambient.rgb;
+    for (var i = 0u; i < min(lit.count, MAX_LIGHTS); i++) {
+        let to_light = lit.lights[i].xyz - vec3<f32>(mesh.world_position.xy, 0.0);
+        let falloff = clamp(1.0 - length(to_light.xy) / lit.lights[i].w, 0.0, 1.0);
+        let facing = max(dot(normal, normalize(to_light)), 0.0);
+        light += lit.colors[i].rgb * facing * falloff * falloff;
+    }
+
+    return vec4<f32>(albedo.rgb * light, albedo.a);
+}
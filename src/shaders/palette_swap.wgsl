@@ -0,0 +1,32 @@
+// Palette swap for sprites: every texel close to palette.from[i] is replaced by palette.to[i]
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+const MAX_PALETTE_COLORS: u32 = 16u;
+const MATCH_DISTANCE: f32 = 0.01;
+
+struct PaletteSwap {
+    from: array<vec4<f32>, MAX_PALETTE_COLORS>,
+    to: array<vec4<f32>, MAX_PALETTE_COLORS>,
+    // atlas frame as (min u, min v, width, height)
+    uv_rect: vec4<f32>,
+    count: u32,
+};
+
+@group(2) @binding(0) var<uniform> palette: PaletteSwap;
+@group(2) @binding(1) var base_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_sampler: sampler;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = palette.uv_rect.xy + mesh.uv * palette.uv_rect.zw;
+    var color = textureSample(base_texture, base_sampler, uv);
+
+    for (var i = 0u; i < min(palette.count, MAX_PALETTE_COLORS); i++) {
+        if distance(color.rgb, palette.from[i].rgb) < MATCH_DISTANCE {
+            color = vec4<f32>(palette.to[i].rgb, color.a);
+            break;
+        }
+    }
+
+    return color;
+}
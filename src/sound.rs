@@ -1,44 +1,364 @@
-use crate::ron_helpers::{parse, trim_extension};
+use crate::camera::CameraView;
+use crate::embedded::{EmbeddedAssets, EMBEDDED_SOURCE};
+use crate::gfx::{update_animations, AnimationFinished};
+use crate::pause::GamePaused;
+use crate::rng::GameRng;
+use crate::ron_helpers::{
+    find_config, loaded_config, parse_list_as, read_config_file, register_ron_asset,
+    trim_extension, ConfigFormat, RonAsset,
+};
 use bevy::{
     app::{App, Plugin},
     asset::AssetServer,
-    audio::{AudioSource, AudioSourceBundle, PlaybackMode, PlaybackSettings},
+    audio::{
+        AudioSource, AudioSourceBundle, Decodable, PlaybackMode, PlaybackSettings, Source,
+        SpatialListener, Volume,
+    },
+    ecs::system::SystemParam,
     log::info,
     prelude::*,
 };
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    time::Duration,
+};
+
+pub struct SoundPlugin {
+    pub sounds_dir: String, // folder containing config.ron and the sound files, relative to the Bevy asset root
+    pub embedded: Option<EmbeddedAssets>, // reads the config and sounds from the binary instead of the assets folder
+    pub pause_sfx: bool,                  // GamePaused also pauses playing SFX, not just the music
+    pub max_distance: f32, // world units from the camera center at which positioned SFX fade to silence
+    pub event_sounds: EventSounds, // filled by on_event, leave it as ..default() in struct literals
+}
+
+/// The sounds SoundPlugin::on_event plays for event types. Entries can only be added through the
+/// builder, the field on SoundPlugin is public so struct literals with `..default()` still work
+#[derive(Default)]
+pub struct EventSounds {
+    registrations: Vec<Box<dyn Fn(&mut App) + Send + Sync>>,
+}
+
+impl Default for SoundPlugin {
+    fn default() -> Self {
+        SoundPlugin {
+            sounds_dir: "sounds".to_string(),
+            embedded: None,
+            pause_sfx: false,
+            max_distance: 20.0,
+            event_sounds: EventSounds::default(),
+        }
+    }
+}
 
-pub struct SoundPlugin {}
+/// Builder methods, e.g. `SoundPlugin::new().pause_sfx(true).on_event::<Jumped>("jump")`
+impl SoundPlugin {
+    pub fn new() -> Self {
+        SoundPlugin::default()
+    }
+
+    pub fn sounds_dir(mut self, sounds_dir: impl Into<String>) -> Self {
+        self.sounds_dir = sounds_dir.into();
+        self
+    }
+
+    pub fn embedded(mut self, embedded: EmbeddedAssets) -> Self {
+        self.embedded = Some(embedded);
+        self
+    }
+
+    pub fn pause_sfx(mut self, pause_sfx: bool) -> Self {
+        self.pause_sfx = pause_sfx;
+        self
+    }
+
+    pub fn max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Plays a sound effect whenever an event of type `T` is sent, e.g.
+    /// `SoundPlugin::default().on_event::<Jumped>("jump")`. Can be called any number of times
+    ///
+    /// `T` still needs to be added with `app.add_event::<T>()`. For sounds that depend on the event's
+    /// data (pan, which sound), send PlaySFX from your own system instead
+    pub fn on_event<T: Event>(mut self, sound: impl Into<String>) -> Self {
+        let sound = sound.into();
+        self.event_sounds
+            .registrations
+            .push(Box::new(move |app: &mut App| {
+                let sound = sound.clone();
+                app.add_systems(
+                    Update,
+                    (move |mut events: EventReader<T>, mut sfx: EventWriter<PlaySFX>| {
+                        for _ in events.read() {
+                            sfx.send(PlaySFX::new(sound.clone()));
+                        }
+                    })
+                    .before(play_sfx),
+                );
+            }));
+        self
+    }
+}
 
 impl Plugin for SoundPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<PlaySFX>()
-            .add_event::<PlayMusic>()
-            .add_event::<StopMusic>()
-            .add_systems(Startup, load_sounds)
-            .add_systems(
-                Update,
-                (
-                    play_sfx.run_if(on_event::<PlaySFX>()),
-                    play_music.run_if(on_event::<PlayMusic>()),
-                    stop_music.run_if(on_event::<StopMusic>()),
-                ),
-            );
+        if let Some(embedded) = &self.embedded {
+            embedded.register(app, &self.sounds_dir);
+        }
+
+        register_ron_asset(app);
+
+        app.insert_resource(SoundSettings {
+            sounds_dir: self.sounds_dir.clone(),
+            embedded_config: self.embedded.as_ref().map(|embedded| embedded.config),
+            pause_sfx: self.pause_sfx,
+            max_distance: self.max_distance,
+        })
+        .init_resource::<GamePaused>()
+        .init_resource::<MusicVolume>()
+        .init_resource::<MusicDucks>()
+        .init_resource::<SfxCooldowns>()
+        .init_resource::<CategoryVolumes>()
+        .init_resource::<GameRng>()
+        .add_event::<PlaySFX>()
+        .add_event::<PlayMusic>()
+        .add_event::<StopMusic>()
+        .add_event::<StopSFX>()
+        .add_event::<SetMusicVolume>()
+        .add_event::<PushMusicDuck>()
+        .add_event::<PopMusicDuck>()
+        .add_event::<SoundNotFound>()
+        .add_event::<AnimationFinished>()
+        .add_systems(Startup, load_sounds)
+        .add_systems(
+            Update,
+            (
+                tick_sfx_cooldowns.before(play_sfx),
+                play_animation_finish_sounds
+                    .after(update_animations)
+                    .before(play_sfx)
+                    .run_if(on_event::<AnimationFinished>()),
+                play_sfx.run_if(on_event::<PlaySFX>()),
+                play_music.run_if(on_event::<PlayMusic>()),
+                stop_music.run_if(on_event::<StopMusic>()),
+                stop_sfx.run_if(on_event::<StopSFX>()),
+                set_music_volume.run_if(on_event::<SetMusicVolume>()),
+                fade_music_volume.after(set_music_volume),
+                clear_finished_sounds,
+                update_world_sounds,
+                apply_category_volumes,
+                update_sfx_envelopes,
+                load_pending_sounds_config.run_if(resource_exists::<PendingSoundsConfig>),
+                apply_audio_debug,
+                apply_game_pause,
+            ),
+        )
+        .add_systems(
+            Update,
+            update_music_ducks
+                .before(fade_music_volume)
+                .run_if(on_event::<PushMusicDuck>().or_else(on_event::<PopMusicDuck>())),
+        );
+
+        for register in self.event_sounds.registrations.iter() {
+            register(app);
+        }
+
+        // dev builds only, insert AudioDebug manually to use it in a release build
+        #[cfg(debug_assertions)]
+        app.init_resource::<AudioDebug>();
     }
 }
 
-#[derive(Debug, Resource)]
+/// Settings copied from SoundPlugin so systems can read them
+#[derive(Debug, Clone, Resource)]
+pub struct SoundSettings {
+    /// Relative to the Bevy asset root (`assets/` by default), e.g. "sounds" or "levels/forest/sounds"
+    pub sounds_dir: String,
+    pub embedded_config: Option<&'static [u8]>,
+    pub pause_sfx: bool,
+    pub max_distance: f32,
+}
+
+impl SoundSettings {
+    /// Asset path of a file in the sounds folder (embedded or on disk)
+    pub fn asset_path(&self, file: &str) -> String {
+        if self.embedded_config.is_some() {
+            format!("{}{}/{}", EMBEDDED_SOURCE, self.sounds_dir, file)
+        } else {
+            format!("{}/{}", self.sounds_dir, file)
+        }
+    }
+
+    /// Reads and parses the sounds config (embedded or on disk). On disk it can be config.ron, or
+    /// config.json/config.toml with the matching cargo feature. Embedded configs are RON
+    pub fn read_config(&self) -> Result<Vec<SoundConfig>, Box<dyn Error>> {
+        match self.embedded_config {
+            Some(bytes) => parse_sounds_config(bytes, ConfigFormat::Ron)
+                .map_err(|e| format!("{}: {}", self.asset_path("config.ron"), e).into()),
+            None => {
+                let (path, format) =
+                    find_config(&format!("./assets/{}", self.sounds_dir), "config")?;
+                read_config_file(&path, format, parse_sounds_config)
+            }
+        }
+    }
+}
+
+///
+/// SoundMode
+///
+/// How PlaySFX plays a sound, set per sound in sounds/config.ron
+///
+/// * Despawn: plays once and despawns the entity (default)
+/// * Loop: loops until the entity is despawned
+/// * Once: plays once and keeps the entity
+/// * Remove: plays once and removes the audio components from the entity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum SoundMode {
+    #[default]
+    Despawn,
+    Loop,
+    Once,
+    Remove,
+}
+
+impl From<SoundMode> for PlaybackMode {
+    fn from(mode: SoundMode) -> Self {
+        match mode {
+            SoundMode::Despawn => PlaybackMode::Despawn,
+            SoundMode::Loop => PlaybackMode::Loop,
+            SoundMode::Once => PlaybackMode::Once,
+            SoundMode::Remove => PlaybackMode::Remove,
+        }
+    }
+}
+
+/// One sound entry in sounds/config.ron
+///
+/// An entry with `group` and `files` instead of `file` defines a group of variations: each file is
+/// loaded as its own sound, and PlaySFX with the group name plays one of them at random
+#[derive(Debug, Clone, Deserialize)]
+pub struct SoundConfig {
+    #[serde(default)]
+    pub file: String,
+    #[serde(default)]
+    pub group: Option<String>, // e.g. "footstep"
+    #[serde(default)]
+    pub files: Vec<String>, // variations in the group
+    #[serde(default)]
+    pub avoid_repeats: bool, // a group never picks the same variation twice in a row
+    #[serde(default)]
+    pub mode: SoundMode,
+    #[serde(default)]
+    pub cooldown: f32, // minimum seconds between two plays of this sound, 0 = no limit
+    #[serde(default)]
+    pub category: Option<String>, // mixer category, e.g. "ui", "music", "ambient" or "voice" (DEFAULT_CATEGORY if unset)
+}
+
+/// Parses a sounds config, accepting either a list of entries or a plain list of filenames
+pub fn parse_sounds_config(
+    bytes: &[u8],
+    format: ConfigFormat,
+) -> Result<Vec<SoundConfig>, Box<dyn Error>> {
+    parse_list_as::<SoundConfig>(bytes, format).or_else(|e| {
+        parse_list_as::<String>(bytes, format)
+            .map(|files| {
+                files
+                    .into_iter()
+                    .map(|file| SoundConfig {
+                        file,
+                        group: None,
+                        files: Vec::new(),
+                        avoid_repeats: false,
+                        mode: SoundMode::default(),
+                        cooldown: 0.0,
+                        category: None,
+                    })
+                    .collect()
+            })
+            .map_err(|_| e)
+    })
+}
+
+/// Variations of a sound, played at random by PlaySFX with the group name
+#[derive(Debug, Clone, Default)]
+pub struct SoundGroup {
+    pub members: Vec<String>, // sound names
+    pub avoid_repeats: bool,
+}
+
+impl SoundGroup {
+    /// Picks a member at random, skipping `last` when avoid_repeats is set (and there's another)
+    pub fn pick(&self, rng: &mut GameRng, last: Option<&str>) -> Option<&str> {
+        let candidates: Vec<&str> = self
+            .members
+            .iter()
+            .map(|member| member.as_str())
+            .filter(|member| !self.avoid_repeats || self.members.len() < 2 || Some(*member) != last)
+            .collect();
+        candidates.get(rng.index(candidates.len())).copied()
+    }
+}
+
+#[derive(Debug, Default, Resource)]
 pub struct SoundResource {
     map: HashMap<String, Handle<AudioSource>>,
+    modes: HashMap<String, SoundMode>,
+    categories: HashMap<String, String>,
+    groups: HashMap<String, SoundGroup>,
 }
 
 impl SoundResource {
     pub fn new() -> Self {
         SoundResource {
             map: HashMap::new(),
+            modes: HashMap::new(),
+            categories: HashMap::new(),
+            groups: HashMap::new(),
         }
     }
 
+    /// Insert a group of variations, its members must be inserted as sounds too
+    pub fn insert_group(&mut self, name: String, group: SoundGroup) {
+        self.groups.insert(name, group);
+    }
+
+    /// Get a group of variations
+    pub fn group(&self, name: &str) -> Option<&SoundGroup> {
+        self.groups.get(name)
+    }
+
+    /// True if PlaySFX can play `name`, either a sound or a group
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.contains_key(name) || self.groups.contains_key(name)
+    }
+
+    /// Set the mixer category of a sound
+    pub fn set_category(&mut self, name: String, category: impl Into<String>) {
+        self.categories.insert(name, category.into());
+    }
+
+    /// Get the mixer category of a sound (DEFAULT_CATEGORY unless configured)
+    pub fn category(&self, name: &str) -> &str {
+        self.categories
+            .get(name)
+            .map_or(DEFAULT_CATEGORY, |category| category.as_str())
+    }
+
+    /// Set the SoundMode PlaySFX uses for a sound
+    pub fn set_mode(&mut self, name: String, mode: SoundMode) {
+        self.modes.insert(name, mode);
+    }
+
+    /// Get the SoundMode PlaySFX uses for a sound (Despawn unless configured)
+    pub fn mode(&self, name: &str) -> SoundMode {
+        self.modes.get(name).copied().unwrap_or_default()
+    }
+
     /// Insert a new Handle<AudioSource>
     pub fn insert(&mut self, name: String, handle: Handle<AudioSource>) {
         self.map.insert(name, handle.clone());
@@ -48,88 +368,893 @@ impl SoundResource {
     pub fn get(&self, name: &str) -> Option<Handle<AudioSource>> {
         self.map.get(name).cloned()
     }
+
+    /// Iterate over every sound name and Handle<AudioSource>
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Handle<AudioSource>)> {
+        self.map.iter()
+    }
+
+    /// Remove a sound, returning its handle
+    ///
+    /// Once the last handle is dropped Bevy frees the audio. Sounds that are already playing hold
+    /// their own handle, so they finish normally
+    pub fn remove(&mut self, name: &str) -> Option<Handle<AudioSource>> {
+        self.groups.remove(name);
+        self.modes.remove(name);
+        self.categories.remove(name);
+        self.map.remove(name)
+    }
+
+    /// Remove every sound (already playing sounds are not cut off)
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.groups.clear();
+        self.modes.clear();
+        self.categories.clear();
+    }
+
+    /// Names of every loaded sound
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(|name| name.as_str())
+    }
 }
 
 ///
 /// load_sounds: Bevy system
 ///
-/// This system scans the graphics folder for sprite sheets and loads the resources
-/// to the asset server
+/// This system scans the sounds folder for sound files and loads the resources
+/// to the asset server. The folder is set by `SoundPlugin.sounds_dir`
+///
 ///
-pub fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let config = parse::<Vec<String>>("./assets/sounds/config.ron")
-        .expect("Fatal: could not parse sounds/config.ron");
+/// On WASM the config is requested from the asset server unless it's embedded, see
+/// load_pending_sounds_config
+pub fn load_sounds(
+    mut commands: Commands,
+    settings: Res<SoundSettings>,
+    asset_server: Res<AssetServer>,
+    mut cooldowns: ResMut<SfxCooldowns>,
+) {
+    if cfg!(target_arch = "wasm32") && settings.embedded_config.is_none() {
+        commands.init_resource::<SoundResource>();
+        commands.insert_resource(PendingSoundsConfig(
+            asset_server.load(settings.asset_path("config.ron")),
+        ));
+        return;
+    }
+
+    let config = settings
+        .read_config()
+        .unwrap_or_else(|e| panic!("Fatal: could not load the sounds config: {}", e));
+
+    build_sounds(
+        &mut commands,
+        &settings,
+        &asset_server,
+        &mut cooldowns,
+        &config,
+    );
+}
+
+/// Sounds config requested from the asset server, on WASM
+#[derive(Debug, Resource)]
+pub struct PendingSoundsConfig(pub Handle<RonAsset>);
 
+///
+/// load_pending_sounds_config: Bevy system
+///
+/// Builds SoundResource once the PendingSoundsConfig has loaded
+pub fn load_pending_sounds_config(
+    mut commands: Commands,
+    settings: Res<SoundSettings>,
+    asset_server: Res<AssetServer>,
+    ron_assets: Res<Assets<RonAsset>>,
+    pending: Res<PendingSoundsConfig>,
+    mut cooldowns: ResMut<SfxCooldowns>,
+) {
+    let Some(bytes) = loaded_config(&asset_server, &ron_assets, &pending.0) else {
+        return;
+    };
+
+    let config = bytes
+        .and_then(|bytes| parse_sounds_config(&bytes, ConfigFormat::Ron))
+        .unwrap_or_else(|e| {
+            panic!(
+                "Fatal: could not load the sounds config: {}: {}",
+                settings.asset_path("config.ron"),
+                e
+            )
+        });
+
+    build_sounds(
+        &mut commands,
+        &settings,
+        &asset_server,
+        &mut cooldowns,
+        &config,
+    );
+    commands.remove_resource::<PendingSoundsConfig>();
+}
+
+/// Cooldown of a config entry: None for no cooldown, or with a warning for values that aren't a
+/// valid duration (negative, infinite, NaN)
+fn config_cooldown(name: &str, seconds: f32) -> Option<Duration> {
+    match Duration::try_from_secs_f32(seconds) {
+        Ok(cooldown) => (!cooldown.is_zero()).then_some(cooldown),
+        Err(_) => {
+            warn!(
+                "Warning: ignoring invalid cooldown {} for sound {}",
+                seconds, name
+            );
+            None
+        }
+    }
+}
+
+/// Loads every sound in a parsed sounds config, replacing SoundResource
+fn build_sounds(
+    commands: &mut Commands,
+    settings: &SoundSettings,
+    asset_server: &AssetServer,
+    cooldowns: &mut SfxCooldowns,
+    config: &[SoundConfig],
+) {
     let mut sound_resource = SoundResource::new();
 
     config.iter().for_each(|data| {
-        let handle: Handle<AudioSource> = asset_server.load(data);
+        let files = match &data.group {
+            Some(_) => data.files.clone(),
+            None if data.file.is_empty() => {
+                warn!("Warning: skipping sound entry with no file or group");
+                return;
+            }
+            None => vec![data.file.clone()],
+        };
+
+        let mut members = Vec::new();
+        for file in files.iter() {
+            let handle: Handle<AudioSource> = asset_server.load(settings.asset_path(file));
+
+            let name = trim_extension(file);
+            sound_resource.insert(name.clone(), handle);
+            if let Some(category) = &data.category {
+                sound_resource.set_category(name.clone(), category.clone());
+            }
+            sound_resource.set_mode(name.clone(), data.mode);
+            members.push(name);
+
+            info!("Loaded sound file: {}, mode: {:?}", file, data.mode);
+        }
 
-        sound_resource.insert(trim_extension(&data), handle);
+        // a group's cooldown covers every variation
+        let name = match &data.group {
+            Some(group) => group.clone(),
+            None => members[0].clone(),
+        };
+        if let Some(cooldown) = config_cooldown(&name, data.cooldown) {
+            cooldowns.set(name.clone(), cooldown);
+        }
 
-        info!("Loaded sound file: {}", data);
+        if data.group.is_some() {
+            if members.is_empty() {
+                warn!("Warning: sound group {} has no files", name);
+            }
+            info!("Loaded sound group: {}, {} file(s)", name, members.len());
+            sound_resource.insert_group(
+                name,
+                SoundGroup {
+                    members,
+                    avoid_repeats: data.avoid_repeats,
+                },
+            );
+        }
     });
 
     commands.insert_resource(sound_resource);
 }
 
+/// Rate limits for sound effects: PlaySFX requests for a sound are dropped until its cooldown has
+/// passed since it last played, e.g. so a pile of collisions doesn't machine-gun the same sound.
+/// Sounds without a cooldown play freely. Filled from `cooldown` in sounds/config.ron
+#[derive(Debug, Clone, Default, Resource)]
+pub struct SfxCooldowns(pub HashMap<String, Timer>);
+
+impl SfxCooldowns {
+    /// Sets the cooldown of a sound, ready to play straight away
+    pub fn set(&mut self, name: impl Into<String>, cooldown: Duration) {
+        let mut timer = Timer::new(cooldown, TimerMode::Once);
+        timer.tick(cooldown);
+        self.0.insert(name.into(), timer);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    /// True if the sound can play now, and if so starts its cooldown
+    fn try_play(&mut self, name: &str) -> bool {
+        match self.0.get_mut(name) {
+            Some(timer) if !timer.finished() => false,
+            Some(timer) => {
+                timer.reset();
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+pub fn tick_sfx_cooldowns(time: Res<Time<Real>>, mut cooldowns: ResMut<SfxCooldowns>) {
+    for timer in cooldowns.bypass_change_detection().0.values_mut() {
+        timer.tick(time.delta());
+    }
+}
+
+/// Plays a sound with its configured SoundMode (once, by default)
+///
+/// ```ignore
+/// fn jump(mut events: EventWriter<PlaySFX>) {
+///     events.send(PlaySFX::new("jump"));
+/// }
+/// ```
 #[derive(Event)]
 pub struct PlaySFX {
     name: String,
+    pan: Option<f32>,
+    position: Option<Vec2>,
+    fade_in: Duration,
+    fade_out: Duration,
+}
+
+impl PlaySFX {
+    pub fn new(name: impl Into<String>) -> Self {
+        PlaySFX {
+            name: name.into(),
+            pan: None,
+            position: None,
+            fade_in: Duration::ZERO,
+            fade_out: Duration::ZERO,
+        }
+    }
+
+    /// Ramps the volume up from silence over `fade_in` when the sound starts, to avoid a click
+    pub fn with_fade_in(mut self, fade_in: Duration) -> Self {
+        self.fade_in = fade_in;
+        self
+    }
+
+    /// Ramps the volume down to silence over the last `fade_out` of the clip. Needs the clip's
+    /// length, which not every file reports (the sound then just stops as usual). Ignored for
+    /// looping sounds
+    pub fn with_fade_out(mut self, fade_out: Duration) -> Self {
+        self.fade_out = fade_out;
+        self
+    }
+
+    /// Places the sound at a world position: it gets quieter the further the camera center is from
+    /// it, down to silence at `SoundPlugin.max_distance`, and follows the camera while it plays.
+    /// Only the volume changes, there's no left/right panning. Takes precedence over with_pan
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Pans the sound from -1.0 (left) to 1.0 (right), values outside are clamped
+    ///
+    /// This places the sound beside the SpatialListener (one is spawned at the origin if the game
+    /// has none), so it's a simple left/right cue rather than world-positioned audio. Ignored when
+    /// the sound also has a world position
+    pub fn with_pan(mut self, pan: f32) -> Self {
+        self.pan = Some(pan.clamp(-1.0, 1.0));
+        self
+    }
+}
+
+/// Volume envelope of a sound effect played with a fade in or fade out, see SfxEnvelope::gain
+#[derive(Debug, Clone, Component)]
+pub struct SfxEnvelope {
+    pub fade_in: Duration,
+    pub fade_out: Duration,
+    pub elapsed: Duration,
+    pub length: Option<Duration>, // clip length, read from the audio once it's loaded
+    pub volume: f32, // volume without the envelope, kept up to date by the other volume systems
+    looping: bool,
+    length_checked: bool,
+}
+
+impl SfxEnvelope {
+    /// Volume multiplier at the current point of the clip, from 0.0 to 1.0
+    pub fn gain(&self) -> f32 {
+        let elapsed = self.elapsed.as_secs_f32();
+        let fade_in = match self.fade_in.as_secs_f32() {
+            fade_in if fade_in > 0.0 => (elapsed / fade_in).min(1.0),
+            _ => 1.0,
+        };
+        let fade_out = match self.length {
+            Some(length) if !self.looping && !self.fade_out.is_zero() => {
+                ((length.as_secs_f32() - elapsed) / self.fade_out.as_secs_f32()).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        };
+        fade_in.min(fade_out)
+    }
+
+    /// True once there's nothing left to ramp (the fade in is over and there's no fade out)
+    fn is_done(&self) -> bool {
+        self.elapsed >= self.fade_in && (self.fade_out.is_zero() || self.looping)
+    }
+}
+
+/// Sets the volume of a sound effect, through its envelope if it has one
+fn set_sfx_volume(sink: &AudioSink, envelope: Option<Mut<SfxEnvelope>>, volume: f32) {
+    match envelope {
+        Some(mut envelope) => {
+            envelope.volume = volume;
+            sink.set_volume(volume * envelope.gain());
+        }
+        None => sink.set_volume(volume),
+    }
 }
 
+/// A sound effect played with PlaySFX::with_position
+#[derive(Debug, Clone, Copy, Component)]
+pub struct WorldSound {
+    pub position: Vec2,
+}
+
+/// Volume of a world-positioned sound: 1.0 at the camera center, falling off linearly to 0.0 at
+/// max_distance. Full volume if there's no camera
+fn world_volume(position: Vec2, camera_view: &Option<Res<CameraView>>, max_distance: f32) -> f32 {
+    match camera_view {
+        Some(camera_view) if max_distance > 0.0 => {
+            (1.0 - camera_view.center.distance(position) / max_distance).clamp(0.0, 1.0)
+        }
+        _ => 1.0,
+    }
+}
+
+/// Distance between the ears of the listener spawned for panned sounds
+pub const PAN_EAR_GAP: f32 = 2.0;
+
+/// The SpatialListener spawned for panned sounds when the game doesn't have one
+#[derive(Component)]
+pub struct PanListener {}
+
+/// Plays a sound on loop, replacing the current song. Does nothing if that song is already
+/// playing, unless forced
 #[derive(Event)]
 pub struct PlayMusic {
     name: String,
+    force: bool,
 }
 
-#[derive(Event)]
+impl PlayMusic {
+    pub fn new(name: impl Into<String>) -> Self {
+        PlayMusic {
+            name: name.into(),
+            force: false,
+        }
+    }
+
+    /// Restarts the song from the beginning even if it's already playing
+    pub fn with_force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+}
+
+/// Stops the current song
+#[derive(Event, Default)]
 pub struct StopMusic {}
 
+impl StopMusic {
+    pub fn new() -> Self {
+        StopMusic {}
+    }
+}
+
+/// Fades the current song to `target` volume (clamped to 0-1) over `fade`, without restarting it
+///
+/// A zero fade applies the volume immediately, and a new event replaces any fade in progress
+#[derive(Event)]
+pub struct SetMusicVolume {
+    target: f32,
+    fade: Duration,
+}
+
+impl SetMusicVolume {
+    pub fn new(target: f32, fade: Duration) -> Self {
+        SetMusicVolume {
+            target: target.clamp(0.0, 1.0),
+            fade,
+        }
+    }
+}
+
+/// Volume of the music, kept across songs so a new song starts at the same level
+#[derive(Debug, Clone, Resource)]
+pub struct MusicVolume {
+    current: f32,
+    from: f32,
+    target: f32,
+    fade: Duration,
+    elapsed: Duration,
+}
+
+impl Default for MusicVolume {
+    fn default() -> Self {
+        MusicVolume {
+            current: 1.0,
+            from: 1.0,
+            target: 1.0,
+            fade: Duration::ZERO,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl MusicVolume {
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.current != self.target
+    }
+
+    /// Starts a fade from the current level, dropping any fade in progress
+    fn retarget(&mut self, target: f32, fade: Duration) {
+        self.from = self.current;
+        self.target = target;
+        self.fade = fade;
+        self.elapsed = Duration::ZERO;
+        if fade.is_zero() {
+            self.current = target;
+        }
+    }
+
+    fn advance(&mut self, delta: Duration) {
+        self.elapsed = (self.elapsed + delta).min(self.fade);
+        let t = if self.fade.is_zero() {
+            1.0
+        } else {
+            self.elapsed.as_secs_f32() / self.fade.as_secs_f32()
+        };
+        self.current = self.from + (self.target - self.from) * t;
+    }
+}
+
+/// How far to lower the music while a duck is active, and how long the fade down takes
+#[derive(Debug, Clone, Copy)]
+pub struct MusicDuck {
+    pub target_volume: f32, // multiplies the MusicVolume, 0-1
+    pub fade: Duration,
+}
+
+impl MusicDuck {
+    pub fn new(target_volume: f32, fade: Duration) -> Self {
+        MusicDuck {
+            target_volume: target_volume.clamp(0.0, 1.0),
+            fade,
+        }
+    }
+}
+
+/// Lowers the music until a PopMusicDuck with the same id, e.g. "combat" or "dialogue". Pushing an
+/// id that's already active replaces its duck
+#[derive(Event, Debug, Clone)]
+pub struct PushMusicDuck {
+    pub id: String,
+    pub duck: MusicDuck,
+}
+
+impl PushMusicDuck {
+    pub fn new(id: impl Into<String>, target_volume: f32, fade: Duration) -> Self {
+        PushMusicDuck {
+            id: id.into(),
+            duck: MusicDuck::new(target_volume, fade),
+        }
+    }
+}
+
+/// Releases the duck pushed with `id`, fading to the next lowest duck (or full volume) over `fade`.
+/// Does nothing if the id isn't active
+#[derive(Event, Debug, Clone)]
+pub struct PopMusicDuck {
+    pub id: String,
+    pub fade: Duration,
+}
+
+impl PopMusicDuck {
+    pub fn new(id: impl Into<String>, fade: Duration) -> Self {
+        PopMusicDuck {
+            id: id.into(),
+            fade,
+        }
+    }
+}
+
+/// Active music ducks. While any are active the music plays at the lowest of their targets (on top
+/// of MusicVolume), and it returns to full once they've all been popped
+#[derive(Debug, Clone, Default, Resource)]
+pub struct MusicDucks {
+    active: Vec<(String, MusicDuck)>,
+    level: MusicVolume,
+}
+
+impl MusicDucks {
+    /// Current duck multiplier, 1.0 when nothing is ducking the music
+    pub fn current(&self) -> f32 {
+        self.level.current
+    }
+
+    pub fn is_active(&self, id: &str) -> bool {
+        self.active.iter().any(|(active, _)| active == id)
+    }
+
+    fn lowest_target(&self) -> f32 {
+        self.active
+            .iter()
+            .map(|(_, duck)| duck.target_volume)
+            .fold(1.0, f32::min)
+    }
+}
+
+/// Stops every playing instance of a sound effect, e.g. a looping fire crackle when the fire goes
+/// out. Does nothing if none are playing
+#[derive(Event)]
+pub struct StopSFX {
+    name: Option<String>,
+}
+
+impl StopSFX {
+    pub fn new(name: impl Into<String>) -> Self {
+        StopSFX {
+            name: Some(name.into()),
+        }
+    }
+
+    /// Stops every playing sound effect
+    pub fn all() -> Self {
+        StopSFX { name: None }
+    }
+}
+
+/// A sound effect spawned by play_sfx
+#[derive(Debug, Clone, Component)]
+pub struct SfxInstance {
+    pub name: String,
+}
+
+/// Added to every sound spawned by SoundPlugin (music and SFX), and removed once it stops playing
+#[derive(Debug, Clone, Component)]
+pub struct PlayingSound {
+    pub name: String,
+    pub is_music: bool,
+    pub looping: bool,
+}
+
+/// Everything currently playing, e.g. for a debug overlay
+///
+/// ```ignore
+/// fn audio_overlay(playing: PlayingSounds) {
+///     for (sound, volume) in playing.iter() {
+///         info!("{} music: {} looping: {} volume: {:?}", sound.name, sound.is_music, sound.looping, volume);
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct PlayingSounds<'w, 's> {
+    query: Query<'w, 's, (&'static PlayingSound, Option<&'static AudioSink>)>,
+}
+
+impl<'w, 's> PlayingSounds<'w, 's> {
+    /// Each playing sound with its current volume (None until the audio has started)
+    pub fn iter(&self) -> impl Iterator<Item = (&PlayingSound, Option<f32>)> {
+        self.query
+            .iter()
+            .map(|(sound, sink)| (sound, sink.map(|sink| sink.volume())))
+    }
+
+    pub fn count(&self) -> usize {
+        self.query.iter().len()
+    }
+}
+
+/// Sent when PlaySFX or PlayMusic names a sound that isn't in SoundResource
+#[derive(Event, Debug, Clone)]
+pub struct SoundNotFound {
+    pub name: String,
+}
+
+/// The current song, set by play_music and despawned by stop_music
 #[derive(Component)]
-pub struct NowPlaying {}
+pub struct NowPlaying {
+    pub name: String,
+}
+
+/// Read-only view of the current song, e.g. for a jukebox UI
+///
+/// ```ignore
+/// fn highlight_track(music: Music) {
+///     if let Some(name) = music.current_music() { /* ... */ }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct Music<'w, 's> {
+    query: Query<'w, 's, (&'static NowPlaying, Option<&'static AudioSink>)>,
+}
+
+impl<'w, 's> Music<'w, 's> {
+    /// Name of the current song, if there is one (paused or not)
+    pub fn current_music(&self) -> Option<String> {
+        self.query
+            .iter()
+            .next()
+            .map(|(now_playing, _)| now_playing.name.clone())
+    }
+
+    /// True if there's a current song that isn't paused. A song that was just started counts as
+    /// playing even before its audio sink exists
+    pub fn is_music_playing(&self) -> bool {
+        self.query
+            .iter()
+            .any(|(_, sink)| sink.map_or(true, |sink| !sink.is_paused()))
+    }
+}
+
+/// Dev-facing mixer controls: muted sounds play at volume 0, and if `solo` is set only that sound
+/// is audible. Present by default in debug builds only
+#[derive(Debug, Clone, Default, Resource)]
+pub struct AudioDebug {
+    pub muted: HashSet<String>,
+    pub solo: Option<String>,
+}
+
+impl AudioDebug {
+    pub fn is_audible(&self, name: &str) -> bool {
+        !self.muted.contains(name) && self.solo.as_ref().map_or(true, |solo| solo == name)
+    }
+}
+
+/// Mixer category of sounds that don't set one in sounds/config.ron
+pub const DEFAULT_CATEGORY: &str = "sfx";
+
+/// Volume per mixer category (the `category` of each sound in sounds/config.ron), from 0.0 to 1.0,
+/// e.g. for the sliders of an options menu. Categories that were never set play at 1.0
+///
+/// Applies to new and already playing sounds, on top of SetMusicVolume for songs
+#[derive(Debug, Clone, Default, Resource)]
+pub struct CategoryVolumes(pub HashMap<String, f32>);
+
+impl CategoryVolumes {
+    pub fn set_category_volume(&mut self, category: impl Into<String>, volume: f32) {
+        self.0.insert(category.into(), volume.clamp(0.0, 1.0));
+    }
+
+    pub fn volume(&self, category: &str) -> f32 {
+        self.0.get(category).copied().unwrap_or(1.0)
+    }
+
+    /// Volume of a sound's category
+    pub fn sound_volume(&self, sound_resource: &SoundResource, name: &str) -> f32 {
+        self.volume(sound_resource.category(name))
+    }
+}
+
+/// Volume a sound should start at given the (optional) AudioDebug resource
+fn debug_volume(audio_debug: &Option<Res<AudioDebug>>, name: &str) -> Volume {
+    match audio_debug {
+        Some(audio_debug) if !audio_debug.is_audible(name) => Volume::new(0.0),
+        _ => Volume::new(1.0),
+    }
+}
+
+///
+/// play_animation_finish_sounds: Bevy system
+///
+/// Plays the finish sound (`on_finish_sound` in graphics/config.ron) of every animation that
+/// finished, warning once per name about sounds that aren't in SoundResource
+pub fn play_animation_finish_sounds(
+    mut finished: EventReader<AnimationFinished>,
+    mut sfx_events: EventWriter<PlaySFX>,
+    sound_resource: Option<Res<SoundResource>>,
+    mut missing_sounds: Local<HashSet<String>>,
+) {
+    for event in finished.read() {
+        let Some(sound) = &event.finish_sound else {
+            continue;
+        };
+        if sound_resource
+            .as_ref()
+            .is_some_and(|sound_resource| sound_resource.contains(sound))
+        {
+            sfx_events.send(PlaySFX::new(sound.clone()));
+        } else if missing_sounds.insert(sound.clone()) {
+            warn!(
+                "Warning: no sound named {} found for animation {}",
+                sound, event.animation_name
+            );
+        }
+    }
+}
 
 pub fn play_sfx(
     mut commands: Commands,
     mut events: EventReader<PlaySFX>,
+    mut not_found: EventWriter<SoundNotFound>,
     sound_resource: Res<SoundResource>,
+    audio_debug: Option<Res<AudioDebug>>,
+    listener_query: Query<&GlobalTransform, With<SpatialListener>>,
+    mut cooldowns: ResMut<SfxCooldowns>,
+    settings: Res<SoundSettings>,
+    camera_view: Option<Res<CameraView>>,
+    category_volumes: Res<CategoryVolumes>,
+    mut rng: ResMut<GameRng>,
+    mut last_picks: Local<HashMap<String, String>>,
+    mut spawned_listener: Local<bool>,
 ) {
     for event in events.read() {
-        if let Some(handle) = sound_resource.map.get(&event.name) {
-            commands.spawn(AudioSourceBundle {
-                source: handle.clone(),
-                settings: PlaybackSettings {
-                    mode: PlaybackMode::Despawn,
-                    ..default()
+        // a group plays one of its variations, which is then treated like any other sound
+        let name = match sound_resource.group(&event.name) {
+            Some(group) => {
+                let last = last_picks.get(&event.name).map(|last| last.as_str());
+                match group.pick(&mut rng, last) {
+                    Some(member) => member.to_string(),
+                    None => event.name.clone(),
+                }
+            }
+            None => event.name.clone(),
+        };
+
+        if let Some(handle) = sound_resource.get(&name) {
+            if !cooldowns.try_play(&event.name) {
+                debug!("Sound {} is cooling down, skipped", event.name);
+                continue;
+            }
+            if name != event.name {
+                last_picks.insert(event.name.clone(), name.clone());
+            }
+
+            let volume = debug_volume(&audio_debug, &name).get()
+                * category_volumes.sound_volume(&sound_resource, &name)
+                * event.position.map_or(1.0, |position| {
+                    world_volume(position, &camera_view, settings.max_distance)
+                });
+            // a world position takes precedence over a pan
+            let pan = event.pan.filter(|_| event.position.is_none());
+            let envelope =
+                (!event.fade_in.is_zero() || !event.fade_out.is_zero()).then(|| SfxEnvelope {
+                    fade_in: event.fade_in,
+                    fade_out: event.fade_out,
+                    elapsed: Duration::ZERO,
+                    length: None,
+                    volume,
+                    looping: sound_resource.mode(&name) == SoundMode::Loop,
+                    length_checked: false,
+                });
+
+            let mut sound = commands.spawn((
+                AudioSourceBundle {
+                    source: handle,
+                    settings: PlaybackSettings {
+                        mode: sound_resource.mode(&name).into(),
+                        volume: Volume::new(
+                            volume * envelope.as_ref().map_or(1.0, |envelope| envelope.gain()),
+                        ),
+                        spatial: pan.is_some(),
+                        ..default()
+                    },
                 },
-            });
+                SfxInstance { name: name.clone() },
+                PlayingSound {
+                    name: name.clone(),
+                    is_music: false,
+                    looping: sound_resource.mode(&name) == SoundMode::Loop,
+                },
+            ));
+
+            if let Some(position) = event.position {
+                sound.insert(WorldSound { position });
+            }
+            if let Some(envelope) = envelope {
+                sound.insert(envelope);
+            }
+
+            if let Some(pan) = pan {
+                let listener = listener_query
+                    .iter()
+                    .next()
+                    .map(|transform| transform.translation())
+                    .unwrap_or_default();
+
+                sound.insert(TransformBundle::from_transform(
+                    Transform::from_translation(
+                        listener + Vec3::new(pan * PAN_EAR_GAP / 2.0, 0.0, 0.0),
+                    ),
+                ));
+
+                if listener_query.is_empty() && !*spawned_listener {
+                    commands.spawn((
+                        PanListener {},
+                        SpatialListener::new(PAN_EAR_GAP),
+                        TransformBundle::default(),
+                    ));
+                    *spawned_listener = true;
+                }
+            }
         } else {
             warn!("Sound not found: {}", event.name);
+            not_found.send(SoundNotFound {
+                name: event.name.clone(),
+            });
         }
     }
 }
 
 pub fn play_music(
     mut commands: Commands,
-    mut events: EventReader<PlaySFX>,
+    mut events: EventReader<PlayMusic>,
+    mut not_found: EventWriter<SoundNotFound>,
     sound_resource: Res<SoundResource>,
-    playing_query: Query<Entity, With<NowPlaying>>,
+    audio_debug: Option<Res<AudioDebug>>,
+    music_volume: Res<MusicVolume>,
+    music_ducks: Res<MusicDucks>,
+    category_volumes: Res<CategoryVolumes>,
+    playing_query: Query<(Entity, &NowPlaying)>,
 ) {
-    if !playing_query.is_empty() {
-        commands.entity(playing_query.single()).despawn();
-    }
+    let mut current = playing_query
+        .get_single()
+        .ok()
+        .map(|(entity, now_playing)| (entity, now_playing.name.clone()));
 
     for event in events.read() {
         if let Some(handle) = sound_resource.map.get(&event.name) {
-            commands
+            if !event.force
+                && current
+                    .as_ref()
+                    .is_some_and(|(_, name)| name == &event.name)
+            {
+                continue;
+            }
+            if let Some((entity, _)) = current.take() {
+                commands.entity(entity).despawn();
+            }
+
+            let entity = commands
                 .spawn(AudioSourceBundle {
                     source: handle.clone(),
                     settings: PlaybackSettings {
                         mode: PlaybackMode::Loop,
+                        volume: Volume::new(
+                            debug_volume(&audio_debug, &event.name).get()
+                                * music_volume.current
+                                * music_ducks.current()
+                                * category_volumes.sound_volume(&sound_resource, &event.name),
+                        ),
                         ..default()
                     },
                 })
-                .insert(NowPlaying {});
+                .insert((
+                    NowPlaying {
+                        name: event.name.clone(),
+                    },
+                    PlayingSound {
+                        name: event.name.clone(),
+                        is_music: true,
+                        looping: true,
+                    },
+                ))
+                .id();
+            current = Some((entity, event.name.clone()));
+        } else {
+            warn!("Sound not found: {}", event.name);
+            not_found.send(SoundNotFound {
+                name: event.name.clone(),
+            });
         }
     }
 }
@@ -139,3 +1264,414 @@ pub fn stop_music(mut commands: Commands, playing_query: Query<Entity, With<NowP
         commands.entity(playing_query.single()).despawn();
     }
 }
+
+pub fn set_music_volume(
+    mut events: EventReader<SetMusicVolume>,
+    mut music_volume: ResMut<MusicVolume>,
+) {
+    // only the latest event matters, earlier ones in the same frame would be replaced anyway
+    if let Some(event) = events.read().last() {
+        music_volume.retarget(event.target, event.fade);
+    }
+}
+
+///
+/// update_music_ducks: Bevy system
+///
+/// Applies PushMusicDuck and PopMusicDuck, in the order they were sent, and fades toward the
+/// lowest active duck
+pub fn update_music_ducks(
+    mut pushes: EventReader<PushMusicDuck>,
+    mut pops: EventReader<PopMusicDuck>,
+    mut music_ducks: ResMut<MusicDucks>,
+) {
+    let mut fade = None;
+
+    for event in pushes.read() {
+        match music_ducks
+            .active
+            .iter_mut()
+            .find(|(id, _)| *id == event.id)
+        {
+            Some((_, duck)) => *duck = event.duck,
+            None => music_ducks.active.push((event.id.clone(), event.duck)),
+        }
+        fade = Some(event.duck.fade);
+    }
+
+    for event in pops.read() {
+        let before = music_ducks.active.len();
+        music_ducks.active.retain(|(id, _)| *id != event.id);
+        if music_ducks.active.len() != before {
+            fade = Some(event.fade);
+        }
+    }
+
+    let target = music_ducks.lowest_target();
+    if let Some(fade) = fade {
+        if target != music_ducks.level.target {
+            music_ducks.level.retarget(target, fade);
+        }
+    }
+}
+
+///
+/// fade_music_volume: Bevy system
+///
+/// Ramps the current song toward the MusicVolume target and the lowest MusicDucks target
+pub fn fade_music_volume(
+    time: Res<Time<Real>>,
+    mut music_volume: ResMut<MusicVolume>,
+    mut music_ducks: ResMut<MusicDucks>,
+    audio_debug: Option<Res<AudioDebug>>,
+    sound_resource: Res<SoundResource>,
+    category_volumes: Res<CategoryVolumes>,
+    playing_query: Query<(&NowPlaying, &AudioSink)>,
+) {
+    if !music_volume.is_changed()
+        && !music_volume.is_fading()
+        && !music_ducks.is_changed()
+        && !music_ducks.level.is_fading()
+    {
+        return;
+    }
+
+    music_volume.advance(time.delta());
+    music_ducks.level.advance(time.delta());
+
+    for (now_playing, sink) in playing_query.iter() {
+        sink.set_volume(
+            debug_volume(&audio_debug, &now_playing.name).get()
+                * music_volume.current
+                * music_ducks.current()
+                * category_volumes.sound_volume(&sound_resource, &now_playing.name),
+        );
+    }
+}
+
+pub fn stop_sfx(
+    mut commands: Commands,
+    mut events: EventReader<StopSFX>,
+    sfx_query: Query<(Entity, &SfxInstance)>,
+) {
+    let names: Vec<Option<String>> = events.read().map(|event| event.name.clone()).collect();
+
+    // each instance is despawned at most once, even if several events match it
+    sfx_query
+        .iter()
+        .filter(|(_, sfx)| {
+            names
+                .iter()
+                .any(|name| name.as_ref().map_or(true, |name| name == &sfx.name))
+        })
+        .for_each(|(entity, _)| commands.entity(entity).despawn());
+}
+
+///
+/// update_world_sounds: Bevy system
+///
+/// Adjusts the volume of world-positioned sound effects as the camera moves
+pub fn update_world_sounds(
+    settings: Res<SoundSettings>,
+    camera_view: Option<Res<CameraView>>,
+    audio_debug: Option<Res<AudioDebug>>,
+    sound_resource: Res<SoundResource>,
+    category_volumes: Res<CategoryVolumes>,
+    mut query: Query<(
+        &WorldSound,
+        &SfxInstance,
+        &AudioSink,
+        Option<&mut SfxEnvelope>,
+    )>,
+) {
+    if !camera_view
+        .as_ref()
+        .is_some_and(|camera_view| camera_view.is_changed())
+    {
+        return;
+    }
+
+    for (world_sound, sfx, sink, envelope) in query.iter_mut() {
+        set_sfx_volume(
+            sink,
+            envelope,
+            debug_volume(&audio_debug, &sfx.name).get()
+                * category_volumes.sound_volume(&sound_resource, &sfx.name)
+                * world_volume(world_sound.position, &camera_view, settings.max_distance),
+        );
+    }
+}
+
+///
+/// apply_category_volumes: Bevy system
+///
+/// Updates the volume of every playing sound when CategoryVolumes changes
+pub fn apply_category_volumes(
+    settings: Res<SoundSettings>,
+    category_volumes: Res<CategoryVolumes>,
+    sound_resource: Res<SoundResource>,
+    audio_debug: Option<Res<AudioDebug>>,
+    music_volume: Res<MusicVolume>,
+    music_ducks: Res<MusicDucks>,
+    camera_view: Option<Res<CameraView>>,
+    mut query: Query<(
+        &PlayingSound,
+        &AudioSink,
+        Option<&WorldSound>,
+        Option<&mut SfxEnvelope>,
+    )>,
+) {
+    if !category_volumes.is_changed() {
+        return;
+    }
+
+    for (playing, sink, world_sound, envelope) in query.iter_mut() {
+        let mut volume = debug_volume(&audio_debug, &playing.name).get()
+            * category_volumes.sound_volume(&sound_resource, &playing.name);
+        if playing.is_music {
+            volume *= music_volume.current * music_ducks.current();
+        }
+        if let Some(world_sound) = world_sound {
+            volume *= world_volume(world_sound.position, &camera_view, settings.max_distance);
+        }
+        set_sfx_volume(sink, envelope, volume);
+    }
+}
+
+///
+/// update_sfx_envelopes: Bevy system
+///
+/// Ramps the volume of sound effects with an SfxEnvelope, and removes the envelope once the fade
+/// in is over and there's no fade out left to apply
+pub fn update_sfx_envelopes(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut query: Query<(Entity, &mut SfxEnvelope, &AudioSink, &Handle<AudioSource>)>,
+) {
+    for (entity, mut envelope, sink, handle) in query.iter_mut() {
+        if sink.is_paused() {
+            continue;
+        }
+
+        // decoded only once, many formats don't report a length at all
+        if !envelope.length_checked && !envelope.fade_out.is_zero() {
+            if let Some(source) = audio_sources.get(handle) {
+                envelope.length = source.decoder().total_duration();
+                envelope.length_checked = true;
+            }
+        }
+
+        envelope.elapsed += time.delta();
+        sink.set_volume(envelope.volume * envelope.gain());
+
+        if envelope.is_done() {
+            commands.entity(entity).remove::<SfxEnvelope>();
+        }
+    }
+}
+
+///
+/// clear_finished_sounds: Bevy system
+///
+/// Removes PlayingSound from sounds that have stopped but whose entity is still around (the Once
+/// and Remove modes). Despawn mode sounds take it with them
+pub fn clear_finished_sounds(
+    mut commands: Commands,
+    query: Query<(Entity, Option<&AudioSink>, Has<Handle<AudioSource>>), With<PlayingSound>>,
+) {
+    for (entity, sink, has_source) in query.iter() {
+        // no sink yet just means the sound hasn't started, no source means Remove mode cleaned up
+        let stopped = match sink {
+            Some(sink) => sink.empty(),
+            None => !has_source,
+        };
+        if stopped {
+            commands.entity(entity).remove::<PlayingSound>();
+        }
+    }
+}
+
+///
+/// apply_audio_debug: Bevy system
+///
+/// Mutes or unmutes the current song when AudioDebug changes
+pub fn apply_audio_debug(
+    audio_debug: Option<Res<AudioDebug>>,
+    music_volume: Res<MusicVolume>,
+    music_ducks: Res<MusicDucks>,
+    sound_resource: Res<SoundResource>,
+    category_volumes: Res<CategoryVolumes>,
+    playing_query: Query<(&NowPlaying, &AudioSink)>,
+) {
+    let Some(audio_debug) = audio_debug else {
+        return;
+    };
+    if !audio_debug.is_changed() {
+        return;
+    }
+
+    for (now_playing, sink) in playing_query.iter() {
+        if audio_debug.is_audible(&now_playing.name) {
+            sink.set_volume(
+                music_volume.current
+                    * music_ducks.current()
+                    * category_volumes.sound_volume(&sound_resource, &now_playing.name),
+            );
+        } else {
+            sink.set_volume(0.0);
+        }
+    }
+}
+
+///
+/// apply_game_pause: Bevy system
+///
+/// Pauses or resumes the current song (and SFX if `SoundPlugin.pause_sfx` is set) when GamePaused
+/// changes
+pub fn apply_game_pause(
+    paused: Res<GamePaused>,
+    settings: Res<SoundSettings>,
+    music_query: Query<&AudioSink, With<NowPlaying>>,
+    sfx_query: Query<&AudioSink, Without<NowPlaying>>,
+) {
+    if !paused.is_changed() {
+        return;
+    }
+
+    let sinks = music_query
+        .iter()
+        .chain(sfx_query.iter().filter(|_| settings.pause_sfx));
+
+    for sink in sinks {
+        if paused.is_paused() {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// App running just play_sfx, with the resources it reads
+    fn sfx_app(sound_resource: SoundResource) -> App {
+        let mut app = App::new();
+        app.insert_resource(sound_resource)
+            .insert_resource(SoundSettings {
+                sounds_dir: "sounds".to_string(),
+                embedded_config: None,
+                pause_sfx: false,
+                max_distance: 20.0,
+            })
+            .insert_resource(GameRng::from_seed(1))
+            .init_resource::<SfxCooldowns>()
+            .init_resource::<CategoryVolumes>()
+            .add_event::<PlaySFX>()
+            .add_event::<SoundNotFound>()
+            .add_systems(Update, play_sfx);
+        app
+    }
+
+    fn sfx_instances(app: &mut App) -> usize {
+        app.world.query::<&SfxInstance>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn parses_sound_entries_and_plain_filenames() {
+        let entries = br#"[(file: "fire.ogg", mode: Loop, cooldown: 0.1)]"#;
+        let config = parse_sounds_config(entries, ConfigFormat::Ron).unwrap();
+        assert_eq!(config[0].file, "fire.ogg");
+        assert_eq!(config[0].mode, SoundMode::Loop);
+        assert_eq!(config[0].cooldown, 0.1);
+
+        let filenames = br#"["hit.ogg"]"#;
+        let config = parse_sounds_config(filenames, ConfigFormat::Ron).unwrap();
+        assert_eq!(config[0].file, "hit.ogg");
+        assert_eq!(config[0].mode, SoundMode::Despawn);
+    }
+
+    #[test]
+    fn pan_and_position_builders_keep_each_other() {
+        let event = PlaySFX::new("fire")
+            .with_position(Vec2::new(4.0, 0.0))
+            .with_pan(0.5);
+
+        assert_eq!(event.position, Some(Vec2::new(4.0, 0.0)));
+        assert_eq!(event.pan, Some(0.5));
+    }
+
+    #[test]
+    fn unknown_sounds_send_one_sound_not_found() {
+        let mut app = sfx_app(SoundResource::new());
+        app.world.send_event(PlaySFX::new("bogus"));
+        app.update();
+
+        let events = app.world.resource::<Events<SoundNotFound>>();
+        let names: Vec<String> = events
+            .get_reader()
+            .read(events)
+            .map(|event| event.name.clone())
+            .collect();
+        assert_eq!(names, vec!["bogus".to_string()]);
+        assert_eq!(sfx_instances(&mut app), 0);
+    }
+
+    #[test]
+    fn finished_animations_play_their_finish_sound() {
+        let mut sound_resource = SoundResource::new();
+        sound_resource.insert("poof".to_string(), Handle::default());
+        let mut app = sfx_app(sound_resource);
+        app.add_event::<AnimationFinished>()
+            .add_systems(Update, play_animation_finish_sounds.before(play_sfx));
+
+        let entity = app.world.spawn_empty().id();
+        for finish_sound in [Some("poof".to_string()), None, Some("bogus".to_string())] {
+            app.world.send_event(AnimationFinished {
+                entity,
+                animation_name: "chest_open".to_string(),
+                finish_sound,
+            });
+        }
+        app.update();
+        assert_eq!(sfx_instances(&mut app), 1);
+    }
+
+    #[test]
+    fn invalid_config_cooldowns_are_skipped() {
+        assert_eq!(
+            config_cooldown("hit", 0.5),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(config_cooldown("hit", 0.0), None);
+        for seconds in [-1.0, f32::INFINITY, f32::NAN, f32::MAX] {
+            assert_eq!(config_cooldown("hit", seconds), None);
+        }
+    }
+
+    #[test]
+    fn cooldowns_let_one_of_many_same_frame_requests_play() {
+        let mut sound_resource = SoundResource::new();
+        sound_resource.insert("hit".to_string(), Handle::default());
+        sound_resource.insert("step".to_string(), Handle::default());
+        let mut app = sfx_app(sound_resource);
+        app.world
+            .resource_mut::<SfxCooldowns>()
+            .set("hit", Duration::from_millis(100));
+
+        for _ in 0..10 {
+            app.world.send_event(PlaySFX::new("hit"));
+        }
+        app.update();
+        assert_eq!(sfx_instances(&mut app), 1);
+
+        // sounds without a cooldown play freely
+        for _ in 0..3 {
+            app.world.send_event(PlaySFX::new("step"));
+        }
+        app.update();
+        assert_eq!(sfx_instances(&mut app), 4);
+    }
+}
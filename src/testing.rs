@@ -0,0 +1,119 @@
+use crate::camera::{SplitScreen, PIXELS_PER_UNIT};
+use crate::gfx::{
+    update_animations, AnimationFinished, AnimationFrameEvent, AnimationResource,
+    AnimationTimeScale, AnimationTrigger, GFXSettings, MaxAnimationDelta, SpriteAdded, SpriteMeta,
+    SpriteSheetResource,
+};
+use crate::pause::GamePaused;
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use std::time::Duration;
+
+/// GFXSettings for systems under test, graphics_dir and everything else left at its default
+pub fn test_settings() -> GFXSettings {
+    GFXSettings {
+        graphics_dir: "graphics".to_string(),
+        debug_preview: None,
+        target_aspect: None,
+        embedded_config: None,
+        clear_color: ClearColorConfig::default(),
+        split_screen: SplitScreen::default(),
+        size_from_atlas: false,
+        pixels_per_unit: PIXELS_PER_UNIT,
+        config_files: Vec::new(),
+        strict_config_merge: false,
+    }
+}
+
+///
+/// asset_app
+///
+/// Headless App with MinimalPlugins, the asset plugin, image and atlas layout assets, an empty
+/// SpriteSheetResource and test_settings, for systems that build sprites. No window is spawned
+pub fn asset_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Image>()
+        .init_asset::<TextureAtlasLayout>()
+        .insert_resource(SpriteSheetResource::new())
+        .insert_resource(test_settings());
+    app
+}
+
+///
+/// test_app
+///
+/// asset_app plus the resources and events update_animations needs, with update_animations
+/// already added. Sprites aren't rendered: mark_sprites_added stands in for
+/// add_sprite_from_sprite_meta, so any entity with a SpriteMeta and an Animation is ticked from its
+/// second frame on
+///
+/// Time only moves when `step` is called
+pub fn test_app() -> App {
+    let mut app = asset_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+        .insert_resource(AnimationResource::new())
+        .init_resource::<AnimationTimeScale>()
+        .init_resource::<MaxAnimationDelta>()
+        .init_resource::<GamePaused>()
+        .add_event::<AnimationFrameEvent>()
+        .add_event::<AnimationFinished>()
+        .add_event::<AnimationTrigger>()
+        .add_systems(
+            Update,
+            (
+                update_animations,
+                mark_sprites_added.after(update_animations),
+            ),
+        );
+    app
+}
+
+///
+/// mark_sprites_added: Bevy system
+///
+/// Adds SpriteAdded to every SpriteMeta without one, as add_sprite_from_sprite_meta would once
+/// the sprite is built, so update_animations keeps ticking after a frame change
+pub fn mark_sprites_added(
+    mut commands: Commands,
+    query: Query<Entity, (With<SpriteMeta>, Without<SpriteAdded>)>,
+) {
+    query.iter().for_each(|entity| {
+        commands.entity(entity).insert(SpriteAdded {});
+    });
+}
+
+/// Runs one update of `app` with `delta` as the frame time
+pub fn step(app: &mut App, delta: Duration) {
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(delta));
+    app.update();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{Animation, AnimationType};
+
+    #[test]
+    fn step_ticks_animations() {
+        let mut app = test_app();
+        let entity = app
+            .world
+            .spawn((
+                SpriteMeta {
+                    sheet_name: "sheet".to_string(),
+                    ..default()
+                },
+                Animation::new("sheet".to_string(), vec![0, 1, 2], 0.1, AnimationType::Once),
+            ))
+            .id();
+
+        // the first update only starts the clock and marks the sprite as built
+        step(&mut app, Duration::ZERO);
+        step(&mut app, Duration::from_millis(100));
+        assert_eq!(app.world.get::<SpriteMeta>(entity).unwrap().index, 1);
+
+        // the frame change removed SpriteAdded, the stand-in puts it back
+        step(&mut app, Duration::from_millis(100));
+        assert_eq!(app.world.get::<SpriteMeta>(entity).unwrap().index, 2);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::gfx::GFXSettings;
+use crate::ron_helpers::{
+    find_config, loaded_config, parse_list_as, read_config_file, register_ron_asset,
+    trim_extension, ConfigFormat, RonAsset,
+};
+use bevy::prelude::*;
+use std::{collections::HashMap, error::Error};
+
+pub struct FontPlugin {
+    pub fonts_dir: String, // folder containing config.ron and the font files, relative to the Bevy asset root
+}
+
+impl Default for FontPlugin {
+    fn default() -> Self {
+        FontPlugin {
+            fonts_dir: "fonts".to_string(),
+        }
+    }
+}
+
+impl Plugin for FontPlugin {
+    fn build(&self, app: &mut App) {
+        register_ron_asset(app);
+
+        app.insert_resource(FontSettings {
+            fonts_dir: self.fonts_dir.clone(),
+        })
+        .add_systems(Startup, load_fonts)
+        .add_systems(
+            Update,
+            load_pending_fonts_config.run_if(resource_exists::<PendingFontsConfig>),
+        );
+    }
+}
+
+/// Settings copied from FontPlugin so systems can read them
+#[derive(Debug, Clone, Resource)]
+pub struct FontSettings {
+    /// Relative to the Bevy asset root (`assets/` by default), e.g. "fonts"
+    pub fonts_dir: String,
+}
+
+impl FontSettings {
+    /// Reads and parses the font config, a list of font filenames
+    pub fn read_config(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let (path, format) = find_config(&format!("./assets/{}", self.fonts_dir), "config")?;
+        read_config_file(&path, format, parse_list_as::<String>)
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct FontResource {
+    map: HashMap<String, Handle<Font>>,
+}
+
+impl FontResource {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Insert a new font handle
+    pub fn insert(&mut self, name: String, handle: Handle<Font>) {
+        self.map.insert(name, handle);
+    }
+
+    /// Get a font handle
+    pub fn get(&self, name: &str) -> Option<Handle<Font>> {
+        self.map.get(name).cloned()
+    }
+}
+
+/// On WASM the config is requested from the asset server instead, see load_pending_fonts_config
+pub fn load_fonts(
+    mut commands: Commands,
+    settings: Res<FontSettings>,
+    asset_server: Res<AssetServer>,
+) {
+    if cfg!(target_arch = "wasm32") {
+        commands.init_resource::<FontResource>();
+        commands.insert_resource(PendingFontsConfig(
+            asset_server.load(format!("{}/config.ron", settings.fonts_dir)),
+        ));
+        return;
+    }
+
+    let config = settings
+        .read_config()
+        .unwrap_or_else(|e| panic!("Fatal: could not load the font config: {}", e));
+
+    build_fonts(&mut commands, &settings, &asset_server, &config);
+}
+
+/// Font config requested from the asset server, on WASM
+#[derive(Debug, Resource)]
+pub struct PendingFontsConfig(pub Handle<RonAsset>);
+
+///
+/// load_pending_fonts_config: Bevy system
+///
+/// Builds FontResource once the PendingFontsConfig has loaded
+pub fn load_pending_fonts_config(
+    mut commands: Commands,
+    settings: Res<FontSettings>,
+    asset_server: Res<AssetServer>,
+    ron_assets: Res<Assets<RonAsset>>,
+    pending: Res<PendingFontsConfig>,
+) {
+    let Some(bytes) = loaded_config(&asset_server, &ron_assets, &pending.0) else {
+        return;
+    };
+
+    let config = bytes
+        .and_then(|bytes| parse_list_as::<String>(&bytes, ConfigFormat::Ron))
+        .unwrap_or_else(|e| {
+            panic!(
+                "Fatal: could not load the font config: {}/config.ron: {}",
+                settings.fonts_dir, e
+            )
+        });
+
+    build_fonts(&mut commands, &settings, &asset_server, &config);
+    commands.remove_resource::<PendingFontsConfig>();
+}
+
+/// Loads every font in a parsed font config, replacing FontResource
+fn build_fonts(
+    commands: &mut Commands,
+    settings: &FontSettings,
+    asset_server: &AssetServer,
+    config: &[String],
+) {
+    let mut font_resource = FontResource::new();
+
+    config.iter().for_each(|file| {
+        let handle: Handle<Font> = asset_server.load(format!("{}/{}", settings.fonts_dir, file));
+        font_resource.insert(trim_extension(file), handle);
+
+        info!("Loaded font: {}", file);
+    });
+
+    commands.insert_resource(font_resource);
+}
+
+/// Font size used by spawn_text, in pixels
+pub const TEXT_SIZE: f32 = 16.0;
+
+///
+/// spawn_text
+///
+/// Spawns world-space text (e.g. a damage number) centered on `position`, using a font from
+/// FontResource by filename (without the extension). Falls back to Bevy's default font if there's
+/// no font by that name
+///
+/// The text is scaled down by GFXSettings.pixels_per_unit so its pixels match the sprites' (at the
+/// default 16, a TEXT_SIZE font is one game unit tall like a 16px sprite)
+pub fn spawn_text(
+    commands: &mut Commands,
+    gfx_settings: &GFXSettings,
+    font_resource: &FontResource,
+    font_name: &str,
+    content: impl Into<String>,
+    position: Vec2,
+) -> Entity {
+    let font = font_resource.get(font_name).unwrap_or_else(|| {
+        warn!(
+            "Warning: no font named {} found, using the default",
+            font_name
+        );
+        Handle::default()
+    });
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(
+                content,
+                TextStyle {
+                    font,
+                    font_size: TEXT_SIZE,
+                    color: Color::WHITE,
+                },
+            ),
+            transform: Transform::from_translation(position.extend(0.0))
+                .with_scale(Vec3::splat(1.0 / gfx_settings.pixels_per_unit)),
+            ..default()
+        })
+        .id()
+}
@@ -0,0 +1,276 @@
+use crate::{
+    gfx::{GFXSettings, PendingGraphicsConfig, SpriteMeta, SpriteSheetResource, SPRITE_SIZE},
+    ron_helpers::{loaded_config, parse_bytes, register_ron_asset, RonAsset},
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::path::Path;
+
+pub struct TilemapPlugin {
+    pub map_file: String, // tile grid spawned at startup, relative to the Bevy asset root
+}
+
+impl Default for TilemapPlugin {
+    fn default() -> Self {
+        TilemapPlugin {
+            map_file: "map.ron".to_string(),
+        }
+    }
+}
+
+impl Plugin for TilemapPlugin {
+    fn build(&self, app: &mut App) {
+        register_ron_asset(app);
+
+        app.insert_resource(TilemapSettings {
+            map_file: self.map_file.clone(),
+        })
+        .add_systems(Startup, request_tilemap)
+        .add_systems(
+            Update,
+            spawn_tilemap.run_if(
+                resource_exists::<PendingTilemap>
+                    .and_then(not(resource_exists::<PendingGraphicsConfig>)),
+            ),
+        );
+    }
+}
+
+/// Settings copied from TilemapPlugin so systems can read them
+#[derive(Debug, Clone, Resource)]
+pub struct TilemapSettings {
+    pub map_file: String,
+}
+
+fn default_z() -> f32 {
+    -1.0
+}
+
+/// Contents of map.ron
+///
+/// `tiles` is a list of rows, top to bottom, of atlas indices into `sheet`. Negative indices
+/// (e.g. -1) are empty cells
+#[derive(Debug, Clone, Deserialize)]
+pub struct TilemapConfig {
+    pub sheet: String,
+    pub tile_size: f32, // pixels, the grid spacing is tile_size / GFXSettings.pixels_per_unit world units
+    pub tiles: Vec<Vec<i32>>,
+    #[serde(default)]
+    pub origin: (f32, f32), // world position of the top left tile
+    #[serde(default = "default_z")]
+    pub z: f32, // behind sprites at z = 0 by default
+}
+
+impl TilemapConfig {
+    /// Size of one grid cell in world units, pixels_per_unit is GFXSettings.pixels_per_unit
+    pub fn cell_size(&self, pixels_per_unit: f32) -> f32 {
+        self.tile_size / pixels_per_unit
+    }
+
+    /// World position of the center of a cell
+    pub fn cell_position(&self, row: usize, column: usize, pixels_per_unit: f32) -> Vec3 {
+        // rows count down, grid cells count up
+        let cell = IVec2::new(column as i32, -(row as i32));
+        Vec3::new(self.origin.0, self.origin.1, self.z)
+            + grid_to_world(cell, self.tile_size, pixels_per_unit)
+    }
+}
+
+/// World position (z = 0) of the center of a grid cell, for a grid of tile_size pixel cells
+/// (tile_size / pixels_per_unit world units) with cell (0, 0) centered on the origin. x goes right
+/// and y goes up, like world coordinates. pixels_per_unit is GFXSettings.pixels_per_unit
+pub fn grid_to_world(cell: IVec2, tile_size: f32, pixels_per_unit: f32) -> Vec3 {
+    (cell.as_vec2() * tile_size / pixels_per_unit).extend(0.0)
+}
+
+/// Grid cell containing a world position, the inverse of grid_to_world. Positions on a cell
+/// boundary go to the cell up and to the right
+pub fn world_to_grid(position: Vec2, tile_size: f32, pixels_per_unit: f32) -> IVec2 {
+    (position / (tile_size / pixels_per_unit) + Vec2::splat(0.5))
+        .floor()
+        .as_ivec2()
+}
+
+/// Spawns a sprite at the center of a grid cell (see grid_to_world), keeping the SpriteMeta as
+/// given. Returns the new entity
+pub fn spawn_at_grid(
+    commands: &mut Commands,
+    sprite_meta: SpriteMeta,
+    cell: IVec2,
+    tile_size: f32,
+    pixels_per_unit: f32,
+) -> Entity {
+    commands
+        .spawn((
+            sprite_meta,
+            SpatialBundle::from_transform(Transform::from_translation(grid_to_world(
+                cell,
+                tile_size,
+                pixels_per_unit,
+            ))),
+        ))
+        .id()
+}
+
+/// Parent of every tile spawned from a map, despawn it (recursively) to remove the map
+#[derive(Debug, Component)]
+pub struct Tilemap {}
+
+/// A tile spawned from a map, at its grid cell
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Tile {
+    pub row: usize,
+    pub column: usize,
+}
+
+/// The map file requested at startup
+#[derive(Debug, Resource)]
+pub struct PendingTilemap(pub Handle<RonAsset>);
+
+///
+/// request_tilemap: Bevy system
+///
+/// Starts loading the map file, if there is one
+pub fn request_tilemap(
+    mut commands: Commands,
+    settings: Res<TilemapSettings>,
+    asset_server: Res<AssetServer>,
+) {
+    if !cfg!(target_arch = "wasm32")
+        && !Path::new(&format!("./assets/{}", settings.map_file)).exists()
+    {
+        info!("No tilemap, {} not found", settings.map_file);
+        return;
+    }
+
+    commands.insert_resource(PendingTilemap(asset_server.load(settings.map_file.clone())));
+}
+
+///
+/// spawn_tilemap: Bevy system
+///
+/// Spawns one static sprite per non-empty cell once the map and the graphics config have loaded
+pub fn spawn_tilemap(
+    mut commands: Commands,
+    settings: Res<TilemapSettings>,
+    gfx_settings: Res<GFXSettings>,
+    asset_server: Res<AssetServer>,
+    ron_assets: Res<Assets<RonAsset>>,
+    pending: Res<PendingTilemap>,
+    sprite_sheet_resource: Res<SpriteSheetResource>,
+) {
+    let Some(bytes) = loaded_config(&asset_server, &ron_assets, &pending.0) else {
+        return;
+    };
+    commands.remove_resource::<PendingTilemap>();
+
+    let map = match bytes.and_then(|bytes| parse_bytes::<TilemapConfig>(&bytes)) {
+        Ok(map) => map,
+        Err(e) => {
+            warn!("Warning: could not parse {}: {}", settings.map_file, e);
+            return;
+        }
+    };
+
+    let Some(handle) = sprite_sheet_resource.get(&map.sheet) else {
+        warn!(
+            "Warning: no sprite sheet named {} found for {}",
+            map.sheet, settings.map_file
+        );
+        return;
+    };
+
+    // with size_from_atlas the frames are already sized by their pixels
+    let scale = if gfx_settings.size_from_atlas {
+        1.0
+    } else {
+        map.cell_size(gfx_settings.pixels_per_unit) / SPRITE_SIZE
+    };
+
+    let mut count = 0;
+    commands
+        .spawn((Tilemap {}, SpatialBundle::default()))
+        .with_children(|parent| {
+            for (row, cells) in map.tiles.iter().enumerate() {
+                for (column, index) in cells.iter().enumerate() {
+                    let Ok(index) = usize::try_from(*index) else {
+                        continue;
+                    };
+                    if index >= handle.frame_count() {
+                        warn!(
+                            "Warning: skipping tile at row {}, column {}: {} has no frame {}",
+                            row, column, map.sheet, index
+                        );
+                        continue;
+                    }
+
+                    parent.spawn((
+                        Tile { row, column },
+                        SpriteMeta {
+                            scale,
+                            ..SpriteMeta::from_handle(map.sheet.clone(), handle.clone(), index)
+                        },
+                        SpatialBundle::from_transform(Transform::from_translation(
+                            map.cell_position(row, column, gfx_settings.pixels_per_unit),
+                        )),
+                    ));
+                    count += 1;
+                }
+            }
+        });
+
+    info!("Spawned {} tile(s) from {}", count, settings.map_file);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_cells_map_to_world_centers_and_back() {
+        // 32px tiles are 2 world units at 16 pixels per unit
+        assert_eq!(
+            grid_to_world(IVec2::new(1, -2), 32.0, 16.0),
+            Vec3::new(2.0, -4.0, 0.0)
+        );
+
+        for x in -3..=3 {
+            for y in -3..=3 {
+                let cell = IVec2::new(x, y);
+                assert_eq!(
+                    world_to_grid(grid_to_world(cell, 32.0, 16.0).truncate(), 32.0, 16.0),
+                    cell
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cell_boundaries_go_up_and_to_the_right() {
+        assert_eq!(
+            world_to_grid(Vec2::new(0.99, 0.0), 32.0, 16.0),
+            IVec2::new(0, 0)
+        );
+        assert_eq!(
+            world_to_grid(Vec2::new(1.0, 1.0), 32.0, 16.0),
+            IVec2::new(1, 1)
+        );
+        assert_eq!(
+            world_to_grid(Vec2::new(-1.01, -1.0), 32.0, 16.0),
+            IVec2::new(-1, 0)
+        );
+    }
+
+    #[test]
+    fn grid_spacing_follows_pixels_per_unit() {
+        // 32px tiles are 1 world unit at 32 pixels per unit
+        assert_eq!(
+            grid_to_world(IVec2::new(1, -2), 32.0, 32.0),
+            Vec3::new(1.0, -2.0, 0.0)
+        );
+        assert_eq!(
+            world_to_grid(Vec2::new(1.0, -2.0), 32.0, 32.0),
+            IVec2::new(1, -2)
+        );
+    }
+}
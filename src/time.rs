@@ -0,0 +1,143 @@
+use crate::pause::GamePaused;
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub struct GameTimerPlugin {}
+
+impl Plugin for GameTimerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamePaused>()
+            .init_resource::<GameClock>()
+            .add_systems(PreUpdate, (tick_game_timers, tick_game_clock));
+    }
+}
+
+///
+/// GameTimer
+///
+/// A Timer driven by the same clock as the animations: `Time<Virtual>`, stopped while GamePaused
+/// is set. Use it for gameplay cooldowns and spawn timers so they pause (and slow down) with the
+/// animations instead of running on real time
+///
+/// As a component it's ticked by tick_game_timers before Update. It can also be kept elsewhere
+/// (e.g. in a resource) and ticked manually with `tick`
+#[derive(Debug, Clone, Component)]
+pub struct GameTimer(pub Timer);
+
+impl GameTimer {
+    pub fn from_seconds(seconds: f32, mode: TimerMode) -> Self {
+        GameTimer(Timer::from_seconds(seconds, mode))
+    }
+
+    /// Advances the timer by the virtual frame time, unless the game is paused
+    pub fn tick(&mut self, time: &Time<Virtual>, paused: &GamePaused) -> &Self {
+        if !paused.is_paused() {
+            self.0.tick(time.delta());
+        }
+        self
+    }
+
+    /// True if the timer finished during the last tick
+    pub fn just_finished(&self) -> bool {
+        self.0.just_finished()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.0.finished()
+    }
+
+    /// How far the timer is from 0.0 to 1.0
+    pub fn fraction(&self) -> f32 {
+        self.0.fraction()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.remaining()
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+///
+/// tick_game_timers: Bevy system
+///
+/// Ticks every GameTimer component, so `just_finished` can be checked anywhere in Update
+pub fn tick_game_timers(
+    time: Res<Time<Virtual>>,
+    paused: Res<GamePaused>,
+    mut query: Query<&mut GameTimer>,
+) {
+    query.iter_mut().for_each(|mut timer| {
+        timer.tick(&time, &paused);
+    });
+}
+
+///
+/// GameClock
+///
+/// Total time played: the sum of `Time<Virtual>` deltas, not counting frames where GamePaused is
+/// set. Read this for scores, day/night cycles and stats instead of mixing Real and Virtual time
+#[derive(Debug, Clone, Default, Resource)]
+pub struct GameClock {
+    pub elapsed: Duration,
+}
+
+impl GameClock {
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// Starts counting from zero again, e.g. for a new run
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+}
+
+///
+/// tick_game_clock: Bevy system
+///
+/// Adds the virtual frame time to GameClock, unless the game is paused
+pub fn tick_game_clock(
+    time: Res<Time<Virtual>>,
+    paused: Res<GamePaused>,
+    mut clock: ResMut<GameClock>,
+) {
+    if !paused.is_paused() {
+        clock.elapsed += time.delta();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::step;
+    use bevy::time::TimeUpdateStrategy;
+
+    #[test]
+    fn game_clock_stops_while_paused() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GameTimerPlugin {}))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        let elapsed = |app: &App| app.world.resource::<GameClock>().elapsed;
+
+        // the first update only starts the clock
+        step(&mut app, Duration::ZERO);
+        step(&mut app, Duration::from_millis(100));
+        assert_eq!(elapsed(&app), Duration::from_millis(100));
+
+        app.world.resource_mut::<Time<Virtual>>().pause();
+        step(&mut app, Duration::from_millis(100));
+        assert_eq!(elapsed(&app), Duration::from_millis(100));
+
+        app.world.resource_mut::<Time<Virtual>>().unpause();
+        app.world.resource_mut::<GamePaused>().0 = true;
+        step(&mut app, Duration::from_millis(100));
+        assert_eq!(elapsed(&app), Duration::from_millis(100));
+
+        app.world.resource_mut::<GamePaused>().0 = false;
+        step(&mut app, Duration::from_millis(100));
+        assert_eq!(elapsed(&app), Duration::from_millis(200));
+    }
+}